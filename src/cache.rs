@@ -0,0 +1,182 @@
+extern crate shellexpand;
+
+use chrono::prelude::*;
+use chrono::Duration;
+use serde_json;
+use std::env;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use types::Config;
+
+/// Seconds a cached response is used without asking GitHub whether it changed.
+const DEFAULT_TTL: i64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheMode {
+    /// Use fresh entries, revalidate stale ones.
+    Normal,
+    /// Ignore existing entries, but store new ones.
+    Refresh,
+    /// Never touch the network, using entries however old they are.
+    Offline,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CacheEntry {
+    pub fetched_at: DateTime<Utc>,
+    pub etag: Option<String>,
+    pub body: String,
+}
+
+pub struct Cache {
+    dir: PathBuf,
+    ttl: Duration,
+    mode: CacheMode,
+    /// When the oldest entry served so far was fetched.
+    oldest_served: Mutex<Option<DateTime<Utc>>>,
+}
+
+/// `$XDG_CACHE_HOME/pears`, or `~/.cache/pears` when that isn't set.
+fn default_dir() -> PathBuf {
+    match env::var("XDG_CACHE_HOME") {
+        Ok(ref dir) if !dir.is_empty() => PathBuf::from(dir).join("pears"),
+        _ => PathBuf::from(shellexpand::tilde("~/.cache/pears").to_string()),
+    }
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Names the entry for a request body sent to an API URL, with a 64-bit FNV-1a
+/// hash so that names stay the same across builds. A zero byte separates the
+/// two, which neither contains.
+pub fn key(api_url: &str, body: &str) -> String {
+    let bytes = api_url.bytes().chain(Some(0)).chain(body.bytes());
+    let hash = bytes.fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    });
+    format!("{:016x}", hash)
+}
+
+impl Cache {
+    pub fn new(config: &Config, mode: CacheMode) -> Cache {
+        Cache::in_dir(default_dir(), config, mode)
+    }
+
+    pub fn in_dir(dir: PathBuf, config: &Config, mode: CacheMode) -> Cache {
+        Cache {
+            dir,
+            ttl: Duration::seconds(config.cache_ttl.unwrap_or(DEFAULT_TTL)),
+            mode,
+            oldest_served: Mutex::new(None),
+        }
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.mode == CacheMode::Offline
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    pub fn load(&self, key: &str) -> Option<CacheEntry> {
+        if self.mode == CacheMode::Refresh {
+            return None;
+        }
+        let mut contents = String::new();
+        File::open(self.path(key))
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    /// Writes an entry, ignoring failures since the cache is only an
+    /// optimisation.
+    pub fn store(&self, key: &str, entry: &CacheEntry) {
+        let contents = match serde_json::to_string(entry) {
+            Ok(contents) => contents,
+            Err(_) => return,
+        };
+        let _ = fs::create_dir_all(&self.dir)
+            .and_then(|_| File::create(self.path(key)))
+            .and_then(|mut f| f.write_all(contents.as_bytes()));
+    }
+
+    /// Whether an entry can be used without asking GitHub.
+    pub fn is_fresh(&self, entry: &CacheEntry) -> bool {
+        match self.mode {
+            CacheMode::Normal => Utc::now() - entry.fetched_at < self.ttl,
+            CacheMode::Refresh => false,
+            CacheMode::Offline => true,
+        }
+    }
+
+    /// Notes that an entry's body is being used in place of a response.
+    pub fn serve(&self, entry: &CacheEntry) -> String {
+        let mut oldest = self.oldest_served.lock().unwrap();
+        if oldest.map_or(true, |o| entry.fetched_at < o) {
+            *oldest = Some(entry.fetched_at);
+        }
+        entry.body.clone()
+    }
+
+    /// When the oldest cached data served was fetched, if any was.
+    pub fn data_as_of(&self) -> Option<DateTime<Utc>> {
+        *self.oldest_served.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(age: i64) -> CacheEntry {
+        CacheEntry {
+            fetched_at: Utc::now() - Duration::seconds(age),
+            etag: Some(String::from("\"abc\"")),
+            body: String::from("{\"data\": {}}"),
+        }
+    }
+
+    #[test]
+    fn store_and_load() {
+        let dir = env::temp_dir().join(format!("pears-cache-test-{}", key("test", "store")));
+        let cache = Cache::in_dir(dir.clone(), &Config::default(), CacheMode::Normal);
+        let key = key("https://api.github.com/graphql", "{}");
+
+        assert!(cache.load(&key).is_none());
+        cache.store(&key, &entry(0));
+        let loaded = cache.load(&key).unwrap();
+        assert_eq!(loaded.etag, Some(String::from("\"abc\"")));
+
+        let refresh = Cache::in_dir(dir.clone(), &Config::default(), CacheMode::Refresh);
+        assert!(refresh.load(&key).is_none());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn stable_keys() {
+        // FNV-1a of a lone zero byte.
+        assert_eq!(key("", ""), "af63bd4c8601b7df");
+        assert_ne!(key("ab", "c"), key("a", "bc"));
+    }
+
+    #[test]
+    fn freshness() {
+        let config = Config {
+            cache_ttl: Some(30),
+            ..Default::default()
+        };
+        let normal = Cache::in_dir(PathBuf::new(), &config, CacheMode::Normal);
+        assert!(normal.is_fresh(&entry(10)));
+        assert!(!normal.is_fresh(&entry(60)));
+
+        let offline = Cache::in_dir(PathBuf::new(), &config, CacheMode::Offline);
+        assert!(offline.is_fresh(&entry(60 * 60 * 24)));
+    }
+}
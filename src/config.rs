@@ -7,7 +7,14 @@ use std::fs::File;
 use std::io::prelude::*;
 use std::path::PathBuf;
 
-use types::Config;
+use types::{Config, ConfigRepo, Group, PullRequest};
+
+pub const DEFAULT_HOST: &str = "github.com";
+
+const DEFAULT_WIP_TITLE_PREFIXES: &[&str] = &["WIP", "[WIP]"];
+const DEFAULT_WIP_LABELS: &[&str] = &["WIP"];
+
+const DEFAULT_CHECKOUT_BRANCH: &str = "pr/{number}";
 
 pub fn read_config_file(path: &str) -> Result<Config, Error> {
     let expanded_path = PathBuf::from(shellexpand::tilde(path).to_string());
@@ -19,3 +26,289 @@ pub fn read_config_file(path: &str) -> Result<Config, Error> {
     let config: Config = serde_json::from_str(&contents)?;
     Ok(config)
 }
+
+/// The GraphQL endpoint for a GitHub host. Enterprise Server instances serve it
+/// under `/api` on the host itself.
+pub fn default_api_url(host: &str) -> String {
+    if host == DEFAULT_HOST {
+        String::from("https://api.github.com/graphql")
+    } else {
+        format!("https://{}/api/graphql", host)
+    }
+}
+
+/// Whether `title` starts with `prefix` as a whole word, ignoring case, so that
+/// "WIP" matches "WIP: Fix" but not "Wipe".
+fn has_prefix(title: &str, prefix: &str) -> bool {
+    let title = title.trim_start();
+    title.len() >= prefix.len()
+        && title.is_char_boundary(prefix.len())
+        && title[..prefix.len()].eq_ignore_ascii_case(prefix)
+        && !title[prefix.len()..]
+            .chars()
+            .next()
+            .map_or(false, |c| c.is_alphanumeric())
+}
+
+/// Fills in a repo's endpoint from a less specific level of the config. An
+/// `api_url` is only inherited if the repo doesn't name a different host.
+fn inherit_endpoint(repo: &mut ConfigRepo, host: &Option<String>, api_url: &Option<String>) {
+    if repo.api_url.is_none() && (repo.host.is_none() || repo.host == *host) {
+        repo.api_url = api_url.clone();
+    }
+    if repo.host.is_none() {
+        repo.host = host.clone();
+    }
+}
+
+impl Group {
+    /// The group's repos with the group's endpoint applied to them.
+    pub fn resolved_repos(&self) -> Vec<ConfigRepo> {
+        self.repos
+            .iter()
+            .map(|repo| {
+                let mut repo = repo.clone();
+                inherit_endpoint(&mut repo, &self.host, &self.api_url);
+                repo
+            })
+            .collect()
+    }
+}
+
+impl Config {
+    fn resolve(&self, repo: &ConfigRepo) -> ConfigRepo {
+        let mut repo = repo.clone();
+        inherit_endpoint(&mut repo, &self.host, &self.api_url);
+        repo
+    }
+
+    pub fn host_for(&self, repo: &ConfigRepo) -> String {
+        self.resolve(repo)
+            .host
+            .unwrap_or_else(|| String::from(DEFAULT_HOST))
+    }
+
+    pub fn api_url_for(&self, repo: &ConfigRepo) -> String {
+        let repo = self.resolve(repo);
+        match repo.api_url {
+            Some(api_url) => api_url,
+            None => default_api_url(repo.host.as_ref().map_or(DEFAULT_HOST, |h| h.as_str())),
+        }
+    }
+
+    /// The REST API root next to the repo's GraphQL endpoint: `/graphql`
+    /// becomes `/v3` on Enterprise Server, and is dropped on api.github.com.
+    pub fn rest_url_for(&self, repo: &ConfigRepo) -> String {
+        let api_url = self.api_url_for(repo);
+        let root = api_url.trim_end_matches('/').trim_end_matches("/graphql");
+        if root.ends_with("/api") {
+            format!("{}/v3", root)
+        } else {
+            String::from(root)
+        }
+    }
+
+    /// The token for the repo's host from `tokens`, falling back to `token`.
+    pub fn token_for(&self, repo: &ConfigRepo) -> &str {
+        let host = self.host_for(repo);
+        self.tokens
+            .as_ref()
+            .and_then(|tokens| tokens.get(&host))
+            .unwrap_or(&self.token)
+    }
+
+    /// The reviewers `who` stands for. `me` is the configured user along with
+    /// their teams, anything else a single user login or team slug.
+    pub fn reviewers<'a>(&'a self, who: &'a str) -> Vec<&'a str> {
+        if who != "me" {
+            return vec![who];
+        }
+        let mut reviewers = vec![self.me.as_str()];
+        if let Some(ref teams) = self.teams {
+            reviewers.extend(teams.iter().map(|t| t.as_str()));
+        }
+        reviewers
+    }
+
+    /// The local branch to check a pull request out to, from the
+    /// `checkout_branch` template. `{number}`, `{branch}` and `{author}` stand
+    /// for the PR's number, head branch and author.
+    pub fn checkout_branch_name(&self, pr: &PullRequest) -> String {
+        self.checkout_branch
+            .as_ref()
+            .map_or(DEFAULT_CHECKOUT_BRANCH, |t| t.as_str())
+            .replace("{number}", &pr.number.to_string())
+            .replace(
+                "{branch}",
+                pr.head_ref_name
+                    .as_ref()
+                    .map_or(&pr.number.to_string(), |h| h),
+            )
+            .replace("{author}", &pr.author.login)
+    }
+
+    /// Whether a pull request is a draft, or looks like one by its title or
+    /// labels.
+    pub fn is_work_in_progress(&self, pr: &PullRequest) -> bool {
+        let prefixes = self.wip_title_prefixes.as_ref().map_or_else(
+            || DEFAULT_WIP_TITLE_PREFIXES.to_vec(),
+            |p| p.iter().map(|p| p.as_str()).collect(),
+        );
+        let labels = self.wip_labels.as_ref().map_or_else(
+            || DEFAULT_WIP_LABELS.to_vec(),
+            |l| l.iter().map(|l| l.as_str()).collect(),
+        );
+        pr.is_draft
+            || prefixes.iter().any(|p| has_prefix(&pr.title, p))
+            || pr
+                .labels
+                .nodes
+                .iter()
+                .any(|l| labels.iter().any(|name| l.name.eq_ignore_ascii_case(name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn repo(host: Option<&str>) -> ConfigRepo {
+        ConfigRepo {
+            owner: String::from("dod-ccpo"),
+            name: String::from("atst"),
+            host: host.map(String::from),
+            api_url: None,
+        }
+    }
+
+    #[test]
+    fn reviewers_for_me_include_teams() {
+        let config = Config {
+            me: String::from("luisgov"),
+            teams: Some(vec![String::from("dod-ccpo/frontend")]),
+            ..Default::default()
+        };
+        assert_eq!(config.reviewers("me"), vec!["luisgov", "dod-ccpo/frontend"]);
+        assert_eq!(config.reviewers("backend"), vec!["backend"]);
+    }
+
+    #[test]
+    fn wip_title_prefixes() {
+        assert!(has_prefix("[WIP] CircleCI CD", "[WIP]"));
+        assert!(has_prefix("wip: Coverage", "WIP"));
+        assert!(!has_prefix("Wipe stale sessions", "WIP"));
+        assert!(!has_prefix("WI", "WIP"));
+    }
+
+    #[test]
+    fn checkout_branch_name() {
+        let pr: PullRequest = serde_json::from_str(
+            r#"{
+                "id": "MDExOlB1bGxSZXF1ZXN0MjE4MTEyNTg3",
+                "state": "OPEN",
+                "title": "Help Content",
+                "number": 340,
+                "url": "https://github.com/dod-ccpo/atst/pull/340",
+                "mergeable": "MERGEABLE",
+                "headRefName": "help-content",
+                "createdAt": "2018-09-25T20:41:46Z",
+                "updatedAt": "2018-10-01T12:48:55Z",
+                "author": {"login": "luisgov"},
+                "labels": {"edges": []},
+                "reviews": {"edges": []}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(Config::default().checkout_branch_name(&pr), "pr/340");
+        let config = Config {
+            checkout_branch: Some(String::from("review/{author}/{branch}")),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.checkout_branch_name(&pr),
+            "review/luisgov/help-content"
+        );
+    }
+
+    #[test]
+    fn api_url_defaults_to_github() {
+        let config = Config::default();
+        assert_eq!(
+            config.api_url_for(&repo(None)),
+            "https://api.github.com/graphql"
+        );
+    }
+
+    #[test]
+    fn rest_url_for_host() {
+        let config = Config {
+            host: Some(String::from("ghe.example.com")),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.rest_url_for(&repo(None)),
+            "https://ghe.example.com/api/v3"
+        );
+        assert_eq!(
+            config.rest_url_for(&repo(Some("github.com"))),
+            "https://api.github.com"
+        );
+    }
+
+    #[test]
+    fn api_url_for_enterprise_host() {
+        let config = Config {
+            host: Some(String::from("ghe.example.com")),
+            api_url: Some(String::from("https://ghe.example.com/custom/graphql")),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.api_url_for(&repo(None)),
+            "https://ghe.example.com/custom/graphql"
+        );
+        assert_eq!(
+            config.api_url_for(&repo(Some("other.example.com"))),
+            "https://other.example.com/api/graphql"
+        );
+        assert_eq!(
+            config.api_url_for(&repo(Some("github.com"))),
+            "https://api.github.com/graphql"
+        );
+    }
+
+    #[test]
+    fn group_endpoint_overrides_config() {
+        let config = Config {
+            host: Some(String::from("ghe.example.com")),
+            ..Default::default()
+        };
+        let group = Group {
+            name: String::from("public"),
+            host: Some(String::from("github.com")),
+            api_url: None,
+            repos: vec![repo(None)],
+        };
+        let repos = group.resolved_repos();
+        assert_eq!(
+            config.api_url_for(&repos[0]),
+            "https://api.github.com/graphql"
+        );
+    }
+
+    #[test]
+    fn token_per_host() {
+        let mut tokens = HashMap::new();
+        tokens.insert(String::from("ghe.example.com"), String::from("ghe-token"));
+        let config = Config {
+            token: String::from("github-token"),
+            tokens: Some(tokens),
+            ..Default::default()
+        };
+        assert_eq!(config.token_for(&repo(None)), "github-token");
+        assert_eq!(
+            config.token_for(&repo(Some("ghe.example.com"))),
+            "ghe-token"
+        );
+    }
+}
@@ -1,17 +1,16 @@
 use types;
 
 use chrono::prelude::*;
+use chrono::Duration;
 use console::{Attribute, Color, Style, Term};
+use std::collections::HashMap;
 use std::io;
 
 pub struct PearsDisplay {
     term: Term,
 }
 
-fn ago(timestamp: DateTime<Utc>) -> String {
-    let now = Utc::now();
-    let duration = now - timestamp;
-
+fn humanize(duration: Duration) -> String {
     let days = duration.num_days();
     let hours = duration.num_hours();
     let minutes = duration.num_minutes();
@@ -37,6 +36,164 @@ fn ago(timestamp: DateTime<Utc>) -> String {
     format!("{} {}", count, noun)
 }
 
+fn ago(timestamp: DateTime<Utc>) -> String {
+    humanize(Utc::now() - timestamp)
+}
+
+fn until(timestamp: DateTime<Utc>) -> String {
+    humanize(timestamp - Utc::now())
+}
+
+fn approval_indicator(state: types::ApprovalState) -> &'static str {
+    match state {
+        types::ApprovalState::Approved => "✅ ",
+        types::ApprovalState::ChangesRequested => "❌ ",
+        types::ApprovalState::ReviewRequired | types::ApprovalState::None => "   ",
+    }
+}
+
+/// How a review's state reads in the reviewer matrix.
+fn review_state(state: &str) -> String {
+    match state {
+        "APPROVED" => Style::new().green().apply_to("approved").to_string(),
+        "CHANGES_REQUESTED" => Style::new().red().apply_to("changes requested").to_string(),
+        "COMMENTED" => String::from("commented"),
+        "DISMISSED" => Style::new()
+            .attr(Attribute::Dim)
+            .apply_to("dismissed")
+            .to_string(),
+        other => other.to_lowercase(),
+    }
+}
+
+/// Lines of a diff hunk shown above a review thread, counting back from the
+/// line it is on.
+const DIFF_HUNK_LINES: usize = 8;
+
+/// Widest a `--stat` bar gets, as in `git diff --stat`.
+const DIFF_STAT_WIDTH: usize = 40;
+
+/// A line of a unified diff, colored like `git diff` would.
+fn diff_line(line: &str) -> String {
+    let style = if line.starts_with("@@") {
+        Style::new().cyan()
+    } else if line.starts_with('+') {
+        Style::new().green()
+    } else if line.starts_with('-') {
+        Style::new().red()
+    } else {
+        Style::new()
+    };
+    style.apply_to(line).to_string()
+}
+
+/// The end of a diff hunk, colored like `git diff` would.
+fn diff_hunk(hunk: &str) -> String {
+    let lines: Vec<&str> = hunk.lines().collect();
+    let start = lines.len().saturating_sub(DIFF_HUNK_LINES);
+    lines[start..]
+        .iter()
+        .map(|line| format!("   {}", diff_line(line)))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// `+++--` for a file's changes, scaled so that the largest fits in `width`.
+fn diff_stat_bar(file: &types::ChangedFile, largest: i32) -> String {
+    let changes = (file.additions + file.deletions) as usize;
+    let (additions, deletions) = if largest as usize > DIFF_STAT_WIDTH {
+        let scaled = |n: i32| {
+            let n = n as usize * DIFF_STAT_WIDTH / largest as usize;
+            // Never scale a change away entirely.
+            if n == 0 && changes > 0 {
+                1
+            } else {
+                n
+            }
+        };
+        (
+            if file.additions > 0 {
+                scaled(file.additions)
+            } else {
+                0
+            },
+            if file.deletions > 0 {
+                scaled(file.deletions)
+            } else {
+                0
+            },
+        )
+    } else {
+        (file.additions as usize, file.deletions as usize)
+    };
+    format!(
+        "{}{}",
+        Style::new().green().apply_to("+".repeat(additions)),
+        Style::new().red().apply_to("-".repeat(deletions))
+    )
+}
+
+fn ci_indicator(status: Option<types::CiStatus>) -> String {
+    match status {
+        Some(types::CiStatus::Passing) => Style::new().green().apply_to("✔").to_string(),
+        Some(types::CiStatus::Failing) => Style::new().red().apply_to("✘").to_string(),
+        Some(types::CiStatus::Pending) => Style::new().yellow().apply_to("●").to_string(),
+        None => String::from(" "),
+    }
+}
+
+fn local_branch_state(state: types::LocalBranchState) -> String {
+    match state {
+        types::LocalBranchState::UpToDate => Style::new()
+            .green()
+            .apply_to("[local: up to date]")
+            .to_string(),
+        types::LocalBranchState::Behind(behind) => Style::new()
+            .yellow()
+            .apply_to(format!("[local: {} behind]", behind))
+            .to_string(),
+        types::LocalBranchState::Ahead(ahead) => Style::new()
+            .cyan()
+            .apply_to(format!("[local: {} ahead]", ahead))
+            .to_string(),
+        types::LocalBranchState::Diverged { ahead, behind } => Style::new()
+            .red()
+            .apply_to(format!(
+                "[local: diverged, {} ahead, {} behind]",
+                ahead, behind
+            ))
+            .to_string(),
+        types::LocalBranchState::Unfetched => Style::new()
+            .yellow()
+            .apply_to("[local: behind, not fetched]")
+            .to_string(),
+    }
+}
+
+/// A badge for pull requests that are drafts or no longer open.
+fn state_badge(pr: &types::PullRequest) -> String {
+    match pr.state.as_str() {
+        "MERGED" => Style::new().magenta().apply_to("[merged] ").to_string(),
+        "CLOSED" => Style::new().red().apply_to("[closed] ").to_string(),
+        _ if pr.is_draft => Style::new()
+            .attr(Attribute::Dim)
+            .apply_to("[draft] ")
+            .to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Who opened a pull request and when it last changed state, or was updated
+/// if it is still open.
+fn activity(pr: &types::PullRequest) -> String {
+    let last_event = match (pr.merged_at, pr.closed_at) {
+        (Some(merged_at), _) => format!("Merged {} ago", ago(merged_at)),
+        (None, Some(closed_at)) => format!("Closed {} ago", ago(closed_at)),
+        (None, None) => format!("Updated {} ago", ago(pr.updated_at)),
+    };
+    format!("Opened by {} | {}", pr.author.login, last_event)
+}
+
 impl PearsDisplay {
     pub fn new() -> PearsDisplay {
         let term = Term::stdout();
@@ -50,75 +207,423 @@ impl PearsDisplay {
         self.term.write_line(line.as_str()).unwrap();
     }
 
-    pub fn list(&self, prs: Vec<&types::PullRequest>) {
+    /// Lists pull requests, marking those awaiting my review or from my `fork`
+    /// and dimming those that are still in progress. `local` has the state of
+    /// their local branches, by number.
+    pub fn list(
+        &self,
+        prs: Vec<&types::PullRequest>,
+        config: &types::Config,
+        fork: Option<&types::ConfigRepo>,
+        local: &HashMap<i32, types::LocalBranchState>,
+    ) {
         let url_style = Style::new().attr(Attribute::Dim);
         let number_style = Style::new().green();
         let label_style = Style::new().cyan();
+        let requested_style = Style::new().yellow().bold();
+        let fork_style = Style::new().blue();
+        let wip_style = Style::new().attr(Attribute::Dim);
+        let me = config.reviewers("me");
 
         for pr in prs {
             let label_str = pr
                 .labels
+                .nodes
                 .iter()
                 .map(|l| format!("[{}]", l.name))
                 .collect::<Vec<String>>()
                 .join(" ");
 
-            let approved = if pr.is_approved() { "✅ " } else { "   " };
+            let approved = approval_indicator(pr.approval_state());
+            let requested = if pr.is_review_requested_from(&me) {
+                requested_style.apply_to("[review requested] ").to_string()
+            } else {
+                String::new()
+            };
+            let from_fork = match fork {
+                Some(fork) if pr.is_from(fork) => fork_style.apply_to("[my fork] ").to_string(),
+                _ => String::new(),
+            };
+            let title = if config.is_work_in_progress(pr) {
+                wip_style.apply_to(&pr.title).to_string()
+            } else {
+                pr.title.clone()
+            };
+            let local_state = match local.get(&pr.number) {
+                Some(&state) => format!(" {}", local_branch_state(state)),
+                None => String::new(),
+            };
             let line = format!(
-                "{}{} {} {}\n   Opened by {} | Updated {} ago\n   {}\n",
+                "{}{} {} {}{}{}{} {}\n   {}{}\n   {}\n",
                 approved,
+                ci_indicator(pr.ci_status()),
                 number_style.apply_to(format!("#{}", pr.number)),
-                pr.title,
+                requested,
+                from_fork,
+                state_badge(pr),
+                title,
                 label_style.apply_to(label_str),
-                pr.author.login,
-                ago(pr.updated_at),
+                activity(pr),
+                local_state,
                 url_style.apply_to(&pr.url)
             );
             self.term.write_line(line.as_str()).unwrap();
         }
     }
 
-    pub fn show(&self, pr: types::PullRequest) -> io::Result<()> {
+    /// Shows a pull request in full, leaving out resolved review threads if
+    /// `unresolved_only` is set.
+    pub fn show(&self, pr: types::PullRequest, unresolved_only: bool) -> io::Result<()> {
         let url_style = Style::new().attr(Attribute::Dim);
         let number_style = Style::new().green();
         let label_style = Style::new().cyan();
 
         let label_str = pr
             .labels
+            .nodes
             .iter()
             .map(|l| format!("[{}]", l.name))
             .collect::<Vec<String>>()
             .join(" ");
         let line = format!(
-            "{} {} {}\nOpened by {} | Updated {} ago\n{}\n",
+            "{} {}{} {}\n{}\n{}\n",
             number_style.apply_to(format!("#{}", pr.number)),
+            state_badge(&pr),
             pr.title,
             label_style.apply_to(label_str),
-            pr.author.login,
-            ago(pr.updated_at),
-            url_style.apply_to(pr.url)
+            activity(&pr),
+            url_style.apply_to(&pr.url)
         );
         self.term.write_line(line.as_str()).unwrap();
 
+        let latest_reviews = pr.latest_reviews();
+        let requested: Vec<&str> = pr
+            .review_requests
+            .nodes
+            .iter()
+            .filter_map(|r| r.requested_reviewer.as_ref().and_then(|r| r.name()))
+            .collect();
+        if !latest_reviews.is_empty() || !requested.is_empty() {
+            for review in &latest_reviews {
+                let line = format!("{:24} {}", review.author.login, review_state(&review.state));
+                self.term.write_line(line.as_str())?;
+            }
+            for reviewer in requested {
+                let line = format!(
+                    "{:24} {}",
+                    reviewer,
+                    Style::new().yellow().apply_to("requested")
+                );
+                self.term.write_line(line.as_str())?;
+            }
+            self.term.write_line("")?;
+        }
+
+        if let Some(rollup) = pr.status_check_rollup() {
+            for check in &rollup.contexts.nodes {
+                let description = check
+                    .description()
+                    .map_or(String::new(), |d| format!(" - {}", d));
+                let line = format!(
+                    "{} {}{} {}",
+                    ci_indicator(Some(check.status())),
+                    check.name(),
+                    description,
+                    url_style.apply_to(check.url().unwrap_or(""))
+                );
+                self.term.write_line(line.as_str())?;
+            }
+            self.term.write_line("")?;
+        }
+
         if let Some(body) = pr.body {
             self.term.write_line("--------------------")?;
             self.term.write_line(body.as_str())?;
             self.term.write_line("--------------------\n")?;
         }
 
-        let mut comments = pr.comments;
-        comments.sort_by(|a, b| a.updated_at.cmp(&b.updated_at));
+        // The conversation: comments, and what reviews said beyond their state.
+        let mut conversation: Vec<(DateTime<Utc>, String)> = pr
+            .comments
+            .nodes
+            .iter()
+            .map(|comment| {
+                let line = format!(
+                    "{}, {} ago\n   {}\n",
+                    comment.author.login,
+                    ago(comment.created_at),
+                    &comment.body_text
+                );
+                (comment.updated_at, line)
+            })
+            .collect();
+        for review in &pr.reviews.nodes {
+            if review.body_text.is_empty() {
+                continue;
+            }
+            let line = format!(
+                "{} {}, {} ago\n   {}\n",
+                review.author.login,
+                review_state(&review.state),
+                ago(review.created_at),
+                &review.body_text
+            );
+            conversation.push((review.created_at, line));
+        }
+        conversation.sort_by_key(|entry| entry.0);
+
+        for (_, line) in conversation {
+            self.term.write_line(line.as_str())?;
+        }
 
-        for comment in comments {
+        for thread in pr.review_threads.nodes {
+            if unresolved_only && thread.is_resolved {
+                continue;
+            }
+            let location = match thread.line {
+                Some(line) => format!("{}:{}", thread.path, line),
+                None => thread.path.clone(),
+            };
+            let mut badges = String::new();
+            if thread.is_resolved {
+                badges.push_str(" [resolved]");
+            }
+            if thread.is_outdated {
+                badges.push_str(" [outdated]");
+            }
             let line = format!(
-                "{}, {} ago\n   {}\n",
-                comment.author.login,
-                ago(comment.created_at),
-                &comment.body_text
+                "{}{}",
+                Style::new().bold().apply_to(location),
+                url_style.apply_to(badges)
             );
             self.term.write_line(line.as_str())?;
+            if let Some(hunk) = thread.diff_hunk() {
+                self.term.write_line(diff_hunk(hunk).as_str())?;
+            }
+            for comment in &thread.comments.nodes {
+                let line = format!(
+                    "   {}, {} ago\n      {}",
+                    comment.author.login,
+                    ago(comment.created_at),
+                    &comment.body_text
+                );
+                self.term.write_line(line.as_str())?;
+            }
+            self.term.write_line("")?;
         }
 
         Ok(())
     }
+
+    pub fn merge_conflicts(&self, number: i32, base: &str, paths: &[String]) {
+        if paths.is_empty() {
+            let line = format!("#{} merges cleanly into {}.", number, base);
+            self.term
+                .write_line(&Style::new().green().apply_to(line).to_string())
+                .unwrap();
+            return;
+        }
+        let line = format!(
+            "#{} conflicts with {} in {} file{}:",
+            number,
+            base,
+            paths.len(),
+            if paths.len() == 1 { "" } else { "s" }
+        );
+        self.term
+            .write_line(&Style::new().red().apply_to(line).to_string())
+            .unwrap();
+        for path in paths {
+            self.term.write_line(&format!("   {}", path)).unwrap();
+        }
+    }
+
+    pub fn fork_parent(&self, fork: &types::ConfigRepo, parent: &types::ConfigRepo) {
+        let note_style = Style::new().attr(Attribute::Dim);
+        let line = format!(
+            "{}/{} is a fork of {}/{}, so listing pull requests there. Use --fork-only to list the fork's.\n",
+            fork.owner, fork.name, parent.owner, parent.name
+        );
+        self.term
+            .write_line(&note_style.apply_to(line).to_string())
+            .unwrap();
+    }
+
+    pub fn draft_status(&self, number: i32, draft: bool) {
+        let line = if draft {
+            format!("#{} is a draft.", number)
+        } else {
+            format!("#{} is ready for review.", number)
+        };
+        self.term.write_line(line.as_str()).unwrap();
+    }
+
+    pub fn review_submitted(&self, number: i32, event: types::ReviewEvent) {
+        let line = match event {
+            types::ReviewEvent::Approve => format!("Approved #{}.", number),
+            types::ReviewEvent::RequestChanges => format!("Requested changes to #{}.", number),
+            types::ReviewEvent::Comment => format!("Commented on #{}.", number),
+        };
+        self.term.write_line(line.as_str()).unwrap();
+    }
+
+    pub fn checked_out(&self, number: i32, branch: &str) {
+        let line = format!("Checked out #{} as {}.", number, branch);
+        self.term.write_line(line.as_str()).unwrap();
+    }
+
+    pub fn forced_merge(&self, number: i32, reasons: &str) {
+        let warning_style = Style::new().yellow();
+        let line = format!("Merging #{} anyway:\n{}", number, reasons);
+        self.term
+            .write_line(&warning_style.apply_to(line).to_string())
+            .unwrap();
+    }
+
+    pub fn merged(&self, number: i32, method: types::MergeMethod) {
+        let line = match method {
+            types::MergeMethod::Merge => format!("Merged #{}.", number),
+            types::MergeMethod::Squash => format!("Squashed and merged #{}.", number),
+            types::MergeMethod::Rebase => format!("Rebased and merged #{}.", number),
+        };
+        self.term.write_line(line.as_str()).unwrap();
+    }
+
+    pub fn branch_deleted(&self, name: &str) {
+        let line = format!("Deleted branch {}.", name);
+        self.term.write_line(line.as_str()).unwrap();
+    }
+
+    pub fn branch_kept(&self, name: &str) {
+        let warning_style = Style::new().yellow();
+        let line = format!("Not deleting branch {}, it belongs to a fork.", name);
+        self.term
+            .write_line(&warning_style.apply_to(line).to_string())
+            .unwrap();
+    }
+
+    /// Shows each file's patch under a header with its additions and
+    /// deletions.
+    pub fn diff(&self, files: &[types::ChangedFile]) -> io::Result<()> {
+        let path_style = Style::new().bold();
+        let status_style = Style::new().attr(Attribute::Dim);
+
+        for file in files {
+            let renamed = match file.previous_filename {
+                Some(ref previous) => format!("{} → ", previous),
+                None => String::new(),
+            };
+            let line = format!(
+                "{}{} {} {} {}",
+                path_style.apply_to(&renamed),
+                path_style.apply_to(&file.filename),
+                Style::new()
+                    .green()
+                    .apply_to(format!("+{}", file.additions)),
+                Style::new().red().apply_to(format!("-{}", file.deletions)),
+                status_style.apply_to(format!("[{}]", file.status))
+            );
+            self.term.write_line(line.as_str())?;
+
+            match file.patch {
+                Some(ref patch) => {
+                    for line in patch.lines() {
+                        self.term.write_line(&diff_line(line))?;
+                    }
+                }
+                None => self
+                    .term
+                    .write_line(&status_style.apply_to("No diff to show.").to_string())?,
+            }
+            self.term.write_line("")?;
+        }
+        Ok(())
+    }
+
+    /// Summarises the changes to each file like `git diff --stat`.
+    pub fn diff_stat(&self, files: &[types::ChangedFile]) -> io::Result<()> {
+        let width = files.iter().map(|f| f.filename.len()).max().unwrap_or(0);
+        let largest = files
+            .iter()
+            .map(|f| f.additions + f.deletions)
+            .max()
+            .unwrap_or(0);
+        let count_width = largest.to_string().len();
+
+        for file in files {
+            let line = format!(
+                " {:width$} | {:>count_width$} {}",
+                file.filename,
+                file.additions + file.deletions,
+                diff_stat_bar(file, largest),
+                width = width,
+                count_width = count_width
+            );
+            self.term.write_line(line.as_str())?;
+        }
+        let additions: i32 = files.iter().map(|f| f.additions).sum();
+        let deletions: i32 = files.iter().map(|f| f.deletions).sum();
+        let line = format!(
+            " {} file{} changed, {} insertion{}(+), {} deletion{}(-)",
+            files.len(),
+            if files.len() == 1 { "" } else { "s" },
+            additions,
+            if additions == 1 { "" } else { "s" },
+            deletions,
+            if deletions == 1 { "" } else { "s" }
+        );
+        self.term.write_line(line.as_str())
+    }
+
+    pub fn diff_names(&self, files: &[types::ChangedFile]) -> io::Result<()> {
+        for file in files {
+            self.term.write_line(&file.filename)?;
+        }
+        Ok(())
+    }
+
+    pub fn data_as_of(&self, timestamp: DateTime<Utc>) {
+        let banner_style = Style::new().yellow();
+        let line = format!(
+            "Cached data as of {} ago ({})\n",
+            ago(timestamp),
+            timestamp.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+        );
+        self.term
+            .write_line(&banner_style.apply_to(line).to_string())
+            .unwrap();
+    }
+
+    pub fn rate_limit(&self, rate_limit: &types::RateLimit) {
+        let line = format!(
+            "{} of {} points remaining | Last query cost {} | Resets in {}",
+            rate_limit.remaining,
+            rate_limit.limit,
+            rate_limit.cost,
+            until(rate_limit.reset_at)
+        );
+        self.term.write_line(line.as_str()).unwrap();
+    }
+
+    /// Why a repo's pull requests couldn't be listed.
+    pub fn repo_error(&self, error: &types::PearsError) {
+        let warning_style = Style::new().yellow();
+        let line = format!("{}\n", error.details);
+        self.term
+            .write_line(&warning_style.apply_to(line).to_string())
+            .unwrap();
+    }
+
+    pub fn rate_limit_warning(&self, rate_limit: &types::RateLimit) {
+        let warning_style = Style::new().yellow();
+        let line = format!(
+            "Only {} of {} GitHub API points remaining, resetting in {}.\n",
+            rate_limit.remaining,
+            rate_limit.limit,
+            until(rate_limit.reset_at)
+        );
+        self.term
+            .write_line(&warning_style.apply_to(line).to_string())
+            .unwrap();
+    }
 }
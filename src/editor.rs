@@ -0,0 +1,129 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process::{self, Command};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use types::PearsError;
+
+/// Lines starting with this are left out of the message, as in git.
+const COMMENT_PREFIX: &str = "#";
+
+/// How many names to try for the file to edit before giving up.
+const TEMP_FILE_ATTEMPTS: u32 = 16;
+
+/// `$VISUAL`, then `$EDITOR`, then vi.
+fn editor() -> String {
+    env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| String::from("vi"))
+}
+
+fn strip_comments(message: &str) -> String {
+    message
+        .lines()
+        .filter(|line| !line.starts_with(COMMENT_PREFIX))
+        .collect::<Vec<&str>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Writes `contents` to a new file in the temp directory that only the user
+/// can read. It is never a file that already exists, so nobody else can have
+/// put one there beforehand to read or replace what is written.
+fn create_temp_file(name: &str, contents: &str) -> io::Result<PathBuf> {
+    let mut attempt = 0;
+    loop {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        let path = env::temp_dir().join(format!(
+            "pears-{}-{:08x}{:x}-{}",
+            process::id(),
+            nanos,
+            attempt,
+            name
+        ));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        match options.open(&path) {
+            Ok(mut file) => {
+                file.write_all(contents.as_bytes())?;
+                return Ok(path);
+            }
+            Err(ref e)
+                if e.kind() == io::ErrorKind::AlreadyExists && attempt < TEMP_FILE_ATTEMPTS =>
+            {
+                attempt += 1
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Opens the user's editor on `template` and returns what they wrote, without
+/// comment lines or surrounding whitespace.
+pub fn edit(name: &str, template: &str) -> Result<String, PearsError> {
+    let path = create_temp_file(name, template).map_err(|e| PearsError {
+        details: format!("Could not write the file to edit: {}", e),
+    })?;
+
+    // Through the shell, so that editors given with arguments work.
+    let editor = editor();
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", editor))
+        .arg(&editor)
+        .arg(&path)
+        .status();
+    let message = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+
+    match status {
+        Ok(ref status) if status.success() => (),
+        Ok(status) => {
+            return Err(PearsError {
+                details: format!("{} exited with {}.", editor, status),
+            })
+        }
+        Err(e) => {
+            return Err(PearsError {
+                details: format!("Could not run {}: {}", editor, e),
+            })
+        }
+    }
+    message.map(|m| strip_comments(&m)).map_err(|e| PearsError {
+        details: format!("Could not read {}: {}", path.display(), e),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_are_stripped() {
+        let message = "\nLooks good,\n# not part of it\nthanks!\n\n# Reviewing #340\n";
+        assert_eq!(strip_comments(message), "Looks good,\nthanks!");
+    }
+
+    #[test]
+    fn temp_files_are_new() {
+        let first = create_temp_file("REVIEW", "first").unwrap();
+        let second = create_temp_file("REVIEW", "second").unwrap();
+        assert_ne!(first, second);
+        assert_eq!(fs::read_to_string(&first).unwrap(), "first");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "second");
+        let _ = fs::remove_file(first);
+        let _ = fs::remove_file(second);
+    }
+}
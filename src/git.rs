@@ -1,31 +1,982 @@
-use git2::Repository;
+use git2::build::CheckoutBuilder;
+use git2::{
+    self, Cred, CredentialType, FetchOptions, Oid, RemoteCallbacks, Repository, StatusOptions,
+};
 use regex::Regex;
+use std::collections::HashMap;
+use std::convert;
+use std::env;
+use std::fs;
 use std::path::PathBuf;
 
-use types::ConfigRepo;
+use types::{
+    ConfigRepo, HeadBranch, LocalBranchState, PearsError, PullRequest, PullRequestReference,
+};
 
-pub fn discover_repo(path: PathBuf) -> Option<ConfigRepo> {
-    let repo = Repository::discover(path).expect("Couldn't find git repo.");
-    let origin = repo
-        .find_remote("origin")
-        .expect("Could't find origin remote.");
-    let origin_url = origin.url().expect("No URL for origin remote?");
-    let re = Regex::new(r"github.com[/:](?P<owner>.*)/(?P<name>.*)\.git").unwrap();
-    let captures = re.captures(origin_url).expect("Could not parse repo url.");
+impl convert::From<git2::Error> for PearsError {
+    fn from(e: git2::Error) -> Self {
+        PearsError {
+            details: e.message().to_string(),
+        }
+    }
+}
+
+/// Remotes tried in order when none is configured. In a fork, `upstream` is
+/// where pull requests are made.
+const DEFAULT_REMOTES: [&str; 2] = ["upstream", "origin"];
+
+/// `ssh.github.com` serves SSH over port 443 for networks that block port 22.
+const SSH_OVER_HTTPS_HOST: &str = "ssh.github.com";
+
+/// Whether `name` matches an ssh_config glob, where `*` stands for any run of
+/// characters and `?` for any one.
+fn glob_matches(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.split_first(), name.split_first()) {
+        (None, None) => true,
+        (Some((b'*', rest)), _) => {
+            glob_matches(rest, name) || (!name.is_empty() && glob_matches(pattern, &name[1..]))
+        }
+        (Some((&p, rest)), Some((&n, name_rest))) => {
+            (p == b'?' || p.eq_ignore_ascii_case(&n)) && glob_matches(rest, name_rest)
+        }
+        _ => false,
+    }
+}
+
+/// Whether a `Host` line's patterns match `alias`. Any matching negated
+/// pattern rules it out.
+fn host_patterns_match(patterns: &str, alias: &str) -> bool {
+    let mut matched = false;
+    for pattern in patterns.split_whitespace() {
+        if pattern.starts_with('!') {
+            if glob_matches(&pattern.as_bytes()[1..], alias.as_bytes()) {
+                return false;
+            }
+        } else if glob_matches(pattern.as_bytes(), alias.as_bytes()) {
+            matched = true;
+        }
+    }
+    matched
+}
+
+/// The `HostName` an ssh_config gives for `alias`. As in ssh, the first one
+/// that applies wins.
+fn ssh_hostname(ssh_config: &str, alias: &str) -> Option<String> {
+    let mut applies = true;
+    for line in ssh_config.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let split = line
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(line.len());
+        let keyword = line[..split].to_lowercase();
+        let value = line[split..]
+            .trim_start_matches(|c: char| c.is_whitespace() || c == '=')
+            .trim_end()
+            .trim_matches('"');
+        match keyword.as_str() {
+            "host" => applies = host_patterns_match(value, alias),
+            // Match blocks depend on more than the name, so they aren't followed.
+            "match" => applies = false,
+            "hostname" if applies => return Some(value.replace("%h", alias)),
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Looks an SSH host up in `~/.ssh/config`, where it may be an alias.
+fn resolve_ssh_host(host: &str) -> String {
+    let ssh_config = env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(".ssh").join("config"))
+        .and_then(|path| fs::read_to_string(path).ok());
+    ssh_config
+        .and_then(|ssh_config| ssh_hostname(&ssh_config, host))
+        .unwrap_or_else(|| String::from(host))
+}
+
+/// Parses a remote URL in any of the forms git accepts:
+/// `<scheme>://[<user>[:<password>]@]<host>[:<port>]/<owner>/<repo>[.git]`, or
+/// scp-like `[<user>@]<host>:<owner>/<repo>[.git]`. SSH hosts go through
+/// `resolve_host`, since they may be aliases.
+fn parse_remote_url<F>(url: &str, resolve_host: F) -> Option<ConfigRepo>
+where
+    F: Fn(&str) -> String,
+{
+    let with_scheme = Regex::new(
+        r"^(?P<scheme>[A-Za-z][A-Za-z0-9+.-]*)://(?:[^/]*@)?(?P<host>\[[^\]]+\]|[^/:]+)(?::\d*)?/(?P<path>.+)$",
+    )
+    .unwrap();
+    let scp_like = Regex::new(r"^(?:[^@/]+@)?(?P<host>[^/:]+):(?P<path>.+)$").unwrap();
 
+    let (host, path, over_ssh) = match with_scheme.captures(url) {
+        Some(captures) => {
+            let scheme = captures["scheme"].to_lowercase();
+            let over_ssh = scheme == "ssh" || scheme == "git+ssh" || scheme == "ssh+git";
+            (
+                String::from(&captures["host"]),
+                String::from(&captures["path"]),
+                over_ssh,
+            )
+        }
+        None if !url.contains("://") => {
+            let captures = scp_like.captures(url)?;
+            (
+                String::from(&captures["host"]),
+                String::from(&captures["path"]),
+                true,
+            )
+        }
+        None => return None,
+    };
+
+    let path = path.trim_end_matches('/');
+    let path = path.trim_end_matches(".git");
+    let mut segments = path.rsplit('/').filter(|s| !s.is_empty());
+    let name = segments.next()?;
+    let owner = segments.next()?;
+
+    let mut host = if over_ssh { resolve_host(&host) } else { host };
+    if host.eq_ignore_ascii_case(SSH_OVER_HTTPS_HOST) {
+        host = String::from("github.com");
+    }
     Some(ConfigRepo {
+        owner: String::from(owner),
+        name: String::from(name),
+        host: Some(host.to_lowercase()),
+        api_url: None,
+    })
+}
+
+/// The remote to use: the one asked for, else the first of
+/// `DEFAULT_REMOTES` there is, else the only remote.
+fn pick_remote(repo: &Repository, preferred: Option<&str>) -> Result<String, PearsError> {
+    let remotes = repo.remotes()?;
+    let names: Vec<&str> = remotes.iter().flatten().collect();
+    if let Some(preferred) = preferred {
+        return if names.contains(&preferred) {
+            Ok(String::from(preferred))
+        } else {
+            Err(PearsError {
+                details: format!("There is no remote named {}.", preferred),
+            })
+        };
+    }
+    if let Some(name) = DEFAULT_REMOTES.iter().find(|name| names.contains(name)) {
+        return Ok(name.to_string());
+    }
+    match names.as_slice() {
+        [name] => Ok(name.to_string()),
+        [] => Err(PearsError {
+            details: String::from("This repository has no remotes. Use --repo to name one."),
+        }),
+        _ => Err(PearsError {
+            details: format!(
+                "Not sure which of the remotes {} to use. Pick one with --remote.",
+                names.join(", ")
+            ),
+        }),
+    }
+}
+
+fn remote_repo(repo: &Repository, name: &str) -> Result<ConfigRepo, PearsError> {
+    let remote = repo.find_remote(name)?;
+    let url = remote.url().ok_or_else(|| PearsError {
+        details: format!("The URL of remote {} is not valid UTF-8.", name),
+    })?;
+    parse_remote_url(url, resolve_ssh_host).ok_or_else(|| PearsError {
+        details: format!(
+            "Could not tell which GitHub repo remote {} ({}) is.",
+            name, url
+        ),
+    })
+}
+
+fn open_repo(path: PathBuf) -> Result<Repository, PearsError> {
+    Repository::discover(path).map_err(|_| PearsError {
+        details: String::from("Not in a git repository. Use --repo to name one."),
+    })
+}
+
+/// The GitHub repo of the git repository at `path`, from its `remote`, or
+/// from the one `pick_remote` finds.
+pub fn discover_repo(path: PathBuf, remote: Option<&str>) -> Result<ConfigRepo, PearsError> {
+    let repo = open_repo(path)?;
+    let name = pick_remote(&repo, remote)?;
+    remote_repo(&repo, &name)
+}
+
+/// The name of a remote of the git repository at `path` that points at
+/// `target`, preferring the one `pick_remote` would use and then
+/// `DEFAULT_REMOTES`.
+pub fn remote_for(
+    path: PathBuf,
+    target: &ConfigRepo,
+    preferred: Option<&str>,
+) -> Result<String, PearsError> {
+    let repo = open_repo(path)?;
+    let is_target = |name: &str| {
+        remote_repo(&repo, name).ok().map_or(false, |r| {
+            r.owner.eq_ignore_ascii_case(&target.owner) && r.name.eq_ignore_ascii_case(&target.name)
+        })
+    };
+    let remotes = repo.remotes()?;
+    let mut candidates = pick_remote(&repo, preferred)
+        .ok()
+        .into_iter()
+        .chain(DEFAULT_REMOTES.iter().map(|name| name.to_string()))
+        .chain(remotes.iter().flatten().map(String::from));
+    candidates
+        .find(|name| is_target(name))
+        .ok_or_else(|| PearsError {
+            details: format!("No remote points at {}/{}.", target.owner, target.name),
+        })
+}
+
+pub fn parse_repo_description(description: &str) -> Result<ConfigRepo, PearsError> {
+    let re = Regex::new(r"^(?:(?P<host>[^/]+)/)?(?P<owner>[^/]+)/(?P<name>[^/]+)$").unwrap();
+    let captures = re.captures(description).ok_or_else(|| PearsError {
+        details: format!(
+            "Could not parse repo {}. Use [<host>/]<owner>/<repo>.",
+            description
+        ),
+    })?;
+    Ok(ConfigRepo {
         owner: String::from(&captures["owner"]),
         name: String::from(&captures["name"]),
+        host: captures.name("host").map(|h| String::from(h.as_str())),
+        api_url: None,
     })
 }
 
-pub fn parse_repo_description(description: &str) -> ConfigRepo {
-    let re = Regex::new(r"(?P<owner>.*)/(?P<name>.*)").unwrap();
-    let captures = re
-        .captures(description)
-        .expect("Could not parse repo description.");
-    ConfigRepo {
-        owner: String::from(&captures["owner"]),
+/// Parses `340`, `#340`, `[<host>/]<owner>/<repo>#340` or the URL of a pull
+/// request, including any of its tabs.
+pub fn parse_pull_request_reference(reference: &str) -> Result<PullRequestReference, PearsError> {
+    let url = Regex::new(
+        r"^https?://(?P<host>[^/]+)/(?P<owner>[^/]+)/(?P<name>[^/]+)/pull/(?P<number>\d+)(?:[/?#].*)?$",
+    )
+    .unwrap();
+    let short = Regex::new(
+        r"^(?:(?:(?:(?P<host>[^/#]+)/)?(?P<owner>[^/#]+)/(?P<name>[^/#]+))?#)?(?P<number>\d+)$",
+    )
+    .unwrap();
+    let reference = reference.trim();
+    let captures = url
+        .captures(reference)
+        .or_else(|| short.captures(reference))
+        .ok_or_else(|| PearsError {
+            details: format!(
+                "Could not parse pull request {}. Use a number, owner/repo#number or a URL.",
+                reference
+            ),
+        })?;
+
+    let number = captures["number"].parse::<i32>().map_err(|_| PearsError {
+        details: format!("{} is not a pull request number.", &captures["number"]),
+    })?;
+    let repo = captures.name("owner").map(|owner| ConfigRepo {
+        owner: String::from(owner.as_str()),
         name: String::from(&captures["name"]),
+        host: captures.name("host").map(|h| String::from(h.as_str())),
+        api_url: None,
+    });
+    Ok(PullRequestReference { repo, number })
+}
+
+/// The number in a `refs/pull/<n>/head` ref.
+fn pull_request_ref_number(name: &str) -> Option<i32> {
+    name.strip_prefix("refs/pull/")?
+        .strip_suffix("/head")?
+        .parse()
+        .ok()
+}
+
+/// What on GitHub the branch checked out at `path` is for: the branch or pull
+/// request it tracks if it has an upstream, since `pears checkout` names local
+/// branches its own way, and its own name otherwise. `None` when HEAD isn't on
+/// a branch with commits.
+pub fn head_branch(path: PathBuf) -> Result<Option<HeadBranch>, PearsError> {
+    let repo = Repository::discover(path)?;
+    let head = match repo.head() {
+        Ok(ref head) if head.is_branch() => head.name().map(String::from),
+        Ok(_) => None,
+        Err(ref e) if e.code() == git2::ErrorCode::UnbornBranch => None,
+        Err(e) => return Err(e.into()),
+    };
+    let head = match head {
+        Some(head) => head,
+        None => return Ok(None),
+    };
+
+    let head = head.trim_start_matches("refs/heads/");
+    let merge = match repo.config()?.get_string(&format!("branch.{}.merge", head)) {
+        Ok(merge) => Some(merge),
+        Err(ref e) if e.code() == git2::ErrorCode::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    if let Some(number) = merge.as_ref().and_then(|m| pull_request_ref_number(m)) {
+        return Ok(Some(HeadBranch::PullRequest(number)));
+    }
+    let branch = merge
+        .as_ref()
+        .map_or(head, |m| m.trim_start_matches("refs/heads/"));
+    Ok(Some(HeadBranch::Named(String::from(branch))))
+}
+
+/// The remote and merge ref a local branch tracks, if it tracks one.
+fn tracked_branch(config: &git2::Config, branch: &str) -> Option<(String, String)> {
+    let remote = config
+        .get_string(&format!("branch.{}.remote", branch))
+        .ok()?;
+    let merge = config
+        .get_string(&format!("branch.{}.merge", branch))
+        .ok()?;
+    Some((remote, merge))
+}
+
+fn compare_with_head(repo: &Repository, local: Oid, head: &str) -> LocalBranchState {
+    let head = match Oid::from_str(head) {
+        Ok(head) => head,
+        Err(_) => return LocalBranchState::Unfetched,
+    };
+    match repo.graph_ahead_behind(local, head) {
+        Ok((0, 0)) => LocalBranchState::UpToDate,
+        Ok((0, behind)) => LocalBranchState::Behind(behind),
+        Ok((ahead, 0)) => LocalBranchState::Ahead(ahead),
+        Ok((ahead, behind)) => LocalBranchState::Diverged { ahead, behind },
+        Err(_) => LocalBranchState::Unfetched,
+    }
+}
+
+/// How the local branches of the repository at `path` compare with the heads
+/// of `prs`, pull requests of `repo`, keyed by number. A branch is a pull
+/// request's if it tracks the head branch or `refs/pull/<n>/head` of `repo`,
+/// is named as `checkout_name` would name it, or, unless the pull request is
+/// from a fork, has the head branch's name. Only pull requests of repos some
+/// remote points at are matched.
+pub fn local_branch_states<F>(
+    path: PathBuf,
+    repo: &ConfigRepo,
+    prs: &[&PullRequest],
+    checkout_name: F,
+) -> Result<HashMap<i32, LocalBranchState>, PearsError>
+where
+    F: Fn(&PullRequest) -> String,
+{
+    let local = open_repo(path)?;
+    let config = local.config()?;
+    let remotes: Vec<(String, ConfigRepo)> = local
+        .remotes()?
+        .iter()
+        .flatten()
+        .filter_map(|name| {
+            remote_repo(&local, name)
+                .ok()
+                .map(|r| (String::from(name), r))
+        })
+        .collect();
+    let remotes_for = |owner: &str, name: &str| -> Vec<&str> {
+        remotes
+            .iter()
+            .filter(|(_, r)| {
+                r.owner.eq_ignore_ascii_case(owner) && r.name.eq_ignore_ascii_case(name)
+            })
+            .map(|(remote, _)| remote.as_str())
+            .collect()
+    };
+    let repo_remotes = remotes_for(&repo.owner, &repo.name);
+
+    let mut branches = Vec::new();
+    for branch in local.branches(Some(git2::BranchType::Local))? {
+        let (branch, _) = branch?;
+        if let (Ok(Some(name)), Some(oid)) = (branch.name(), branch.get().target()) {
+            branches.push((String::from(name), oid, tracked_branch(&config, name)));
+        }
+    }
+
+    let mut states = HashMap::new();
+    for pr in prs {
+        let (head_name, head_oid) = match (&pr.head_ref_name, &pr.head_ref_oid) {
+            (Some(name), Some(oid)) => (name, oid),
+            _ => continue,
+        };
+        let head_remotes = match pr.head_repository {
+            Some(ref head) => remotes_for(&head.owner.login, &head.name),
+            None => repo_remotes.clone(),
+        };
+        if head_remotes.is_empty() && repo_remotes.is_empty() {
+            continue;
+        }
+
+        let head_ref = format!("refs/heads/{}", head_name);
+        let checkout = checkout_name(pr);
+        let tracking = branches.iter().find(|(_, _, tracked)| {
+            tracked.as_ref().map_or(false, |(remote, merge)| {
+                (*merge == head_ref && head_remotes.contains(&remote.as_str()))
+                    || (pull_request_ref_number(merge) == Some(pr.number)
+                        && repo_remotes.contains(&remote.as_str()))
+            })
+        });
+        let branch = tracking
+            .or_else(|| branches.iter().find(|(name, _, _)| *name == checkout))
+            .or_else(|| {
+                branches
+                    .iter()
+                    .find(|(name, _, _)| !pr.is_cross_repository && name == head_name)
+            });
+        if let Some(&(_, oid, _)) = branch {
+            states.insert(pr.number, compare_with_head(&local, oid, head_oid));
+        }
+    }
+    Ok(states)
+}
+
+/// Whether any tracked file differs from HEAD, in the index or the working
+/// tree. Untracked files don't count, since checking out leaves them be.
+fn has_uncommitted_changes(repo: &Repository) -> Result<bool, git2::Error> {
+    let mut options = StatusOptions::new();
+    options.include_untracked(false).include_ignored(false);
+    Ok(!repo.statuses(Some(&mut options))?.is_empty())
+}
+
+/// How many times to offer credentials before giving up, since libgit2 keeps
+/// asking for as long as they are rejected.
+const CREDENTIAL_ATTEMPTS: usize = 3;
+
+/// Answers with the SSH agent for SSH remotes and the GitHub token for HTTPS
+/// ones.
+fn fetch_options<'a>(token: &'a str) -> FetchOptions<'a> {
+    let mut attempts = 0;
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |_url, username, allowed| {
+        attempts += 1;
+        if attempts > CREDENTIAL_ATTEMPTS {
+            return Err(git2::Error::from_str("Authentication failed."));
+        }
+        if allowed.contains(CredentialType::SSH_KEY) {
+            Cred::ssh_key_from_agent(username.unwrap_or("git"))
+        } else if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            Cred::userpass_plaintext("x-access-token", token)
+        } else {
+            Cred::default()
+        }
+    });
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(callbacks);
+    options
+}
+
+/// Fetches a pull request's head from `remote_name` and checks it out as
+/// `branch`, creating the branch or fast-forwarding it to the fetched commit.
+/// A branch with commits the pull request doesn't have is left alone. The
+/// branch tracks the head branch when it is the repo's own, and otherwise
+/// `refs/pull/<n>/head`, since branches of forks can only be fetched so.
+pub fn checkout_pull_request(
+    path: PathBuf,
+    remote_name: &str,
+    pr: &PullRequest,
+    branch: &str,
+    token: &str,
+) -> Result<(), PearsError> {
+    let repo = Repository::discover(path)?;
+    if has_uncommitted_changes(&repo)? {
+        return Err(PearsError {
+            details: String::from(
+                "You have uncommitted changes. Commit or stash them before checking out a PR.",
+            ),
+        });
+    }
+
+    let (refspec, fetched_ref, merge) = match pr.head_ref_name {
+        Some(ref head) if !pr.is_cross_repository => (
+            format!("+refs/heads/{}:refs/remotes/{}/{}", head, remote_name, head),
+            format!("refs/remotes/{}/{}", remote_name, head),
+            format!("refs/heads/{}", head),
+        ),
+        _ => {
+            let pull_ref = format!("refs/pull/{}/head", pr.number);
+            (format!("+{0}:{0}", pull_ref), pull_ref.clone(), pull_ref)
+        }
+    };
+    let mut remote = repo.find_remote(remote_name)?;
+    remote.fetch(&[refspec.as_str()], Some(&mut fetch_options(token)), None)?;
+    let commit = repo.find_commit(repo.refname_to_id(&fetched_ref)?)?;
+    let branch_ref = format!("refs/heads/{}", branch);
+    let tip = repo.refname_to_id(&branch_ref).ok();
+    if let Some(tip) = tip {
+        if tip != commit.id() && !repo.graph_descendant_of(commit.id(), tip)? {
+            return Err(PearsError {
+                details: format!(
+                    "{} has commits that #{} doesn't. Push or move them before checking it out again.",
+                    branch, pr.number
+                ),
+            });
+        }
+    }
+    repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().safe()))?;
+    repo.reference(&branch_ref, commit.id(), tip.is_some(), "pears: checkout")?;
+    repo.set_head(&branch_ref)?;
+    let mut config = repo.config()?;
+    config.set_str(&format!("branch.{}.remote", branch), remote_name)?;
+    config.set_str(&format!("branch.{}.merge", branch), &merge)?;
+    Ok(())
+}
+
+/// Conflicted entries of an index are at a stage other than 0: 1 for the
+/// common ancestor, 2 for ours and 3 for theirs.
+const INDEX_STAGE_MASK: u16 = 0x3000;
+
+/// Merges pull request `number`'s head into its base branch in memory, after
+/// fetching both from `remote_name`, and returns the paths that conflict. The
+/// commits to merge are given, so that stale data is noticed. Neither the working
+/// tree nor any branch is touched.
+pub fn merge_conflicts(
+    path: PathBuf,
+    remote_name: &str,
+    number: i32,
+    base_name: &str,
+    base_oid: &str,
+    head_oid: &str,
+    token: &str,
+) -> Result<Vec<String>, PearsError> {
+    let repo = Repository::discover(path)?;
+    let refspecs = [
+        format!("refs/heads/{}", base_name),
+        format!("refs/pull/{}/head", number),
+    ];
+    let refspecs: Vec<&str> = refspecs.iter().map(|r| r.as_str()).collect();
+    repo.find_remote(remote_name)?
+        .fetch(&refspecs, Some(&mut fetch_options(token)), None)?;
+
+    let find = |oid: &str| {
+        Oid::from_str(oid)
+            .and_then(|oid| repo.find_commit(oid))
+            .map_err(|_| PearsError {
+                details: format!(
+                    "{} wasn't fetched from {}. Has the pull request changed?",
+                    oid, remote_name
+                ),
+            })
+    };
+    let base = find(base_oid)?;
+    let head = find(head_oid)?;
+    let ancestor = repo
+        .merge_base(base.id(), head.id())
+        .map_err(|_| PearsError {
+            details: format!("#{} has no history in common with {}.", number, base_name),
+        })?;
+    let ancestor = repo.find_commit(ancestor)?;
+
+    let index = repo.merge_trees(&ancestor.tree()?, &base.tree()?, &head.tree()?, None)?;
+    let mut paths: Vec<String> = index
+        .iter()
+        .filter(|entry| entry.flags & INDEX_STAGE_MASK != 0)
+        .map(|entry| String::from_utf8_lossy(&entry.path).into_owned())
+        .collect();
+    paths.dedup();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use github::{GitHubMockAPI, GithubAPI};
+    use std::path::Path;
+    use types::{Config, StateFilter};
+
+    /// A repository in a fresh temporary directory with a README committed.
+    fn init_repo(name: &str) -> (PathBuf, Repository) {
+        let dir = env::temp_dir().join(format!("pears-git-{}-{}", name, ::std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let repo = Repository::init(&dir).unwrap();
+        fs::write(dir.join("README.md"), "pears\n").unwrap();
+
+        {
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new("README.md")).unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let signature = git2::Signature::now("pears", "pears@example.com").unwrap();
+            repo.commit(Some("HEAD"), &signature, &signature, "Initial", &tree, &[])
+                .unwrap();
+            index.write().unwrap();
+        }
+        (dir, repo)
+    }
+
+    fn parse(url: &str) -> Option<(String, String, String)> {
+        let resolve = |host: &str| {
+            if host == "gh" {
+                String::from("github.com")
+            } else {
+                String::from(host)
+            }
+        };
+        parse_remote_url(url, resolve).map(|r| (r.host.unwrap(), r.owner, r.name))
+    }
+
+    #[test]
+    fn remote_urls() {
+        let atst = Some((
+            String::from("github.com"),
+            String::from("dod-ccpo"),
+            String::from("atst"),
+        ));
+        for url in &[
+            "git@github.com:dod-ccpo/atst.git",
+            "git@github.com:dod-ccpo/atst",
+            "github.com:dod-ccpo/atst/",
+            "gh:dod-ccpo/atst.git",
+            "https://github.com/dod-ccpo/atst.git",
+            "https://github.com/dod-ccpo/atst",
+            "https://x-access-token:s3cr@t@github.com/dod-ccpo/atst.git",
+            "ssh://git@github.com:22/dod-ccpo/atst.git",
+            "ssh://git@ssh.github.com:443/dod-ccpo/atst.git",
+            "ssh://gh/dod-ccpo/atst",
+            "git+ssh://git@github.com/dod-ccpo/atst.git",
+            "git://github.com/dod-ccpo/atst.git",
+        ] {
+            assert_eq!(parse(url), atst, "{}", url);
+        }
+
+        // Only SSH hosts can be aliases.
+        assert_eq!(parse("https://gh/dod-ccpo/atst").unwrap().0, "gh");
+        assert_eq!(
+            parse("https://ghe.example.com:8443/dod-ccpo/atst.git")
+                .unwrap()
+                .0,
+            "ghe.example.com"
+        );
+        assert_eq!(parse("/srv/git/atst.git"), None);
+        assert_eq!(parse("file:///srv/git/atst.git").map(|r| r.2), None);
+        assert_eq!(parse("https://github.com/atst"), None);
+    }
+
+    #[test]
+    fn ssh_config_aliases() {
+        let ssh_config = "
+# Work account
+Host work-gh *.work
+    HostName github.com
+    IdentityFile ~/.ssh/work
+
+Host !skip gh-*
+  Hostname=%h.example.com
+
+Host *
+    User git
+";
+        assert_eq!(
+            ssh_hostname(ssh_config, "work-gh"),
+            Some(String::from("github.com"))
+        );
+        assert_eq!(
+            ssh_hostname(ssh_config, "repo.work"),
+            Some(String::from("github.com"))
+        );
+        assert_eq!(
+            ssh_hostname(ssh_config, "gh-ent"),
+            Some(String::from("gh-ent.example.com"))
+        );
+        assert_eq!(ssh_hostname(ssh_config, "github.com"), None);
+        assert_eq!(
+            ssh_hostname("HostName top.example.com\n", "x"),
+            Some(String::from("top.example.com"))
+        );
+    }
+
+    #[test]
+    fn remotes_prefer_upstream() {
+        let (dir, repo) = init_repo("remotes");
+        assert!(discover_repo(dir.clone(), None).is_err());
+
+        repo.remote("fork", "git@github.com:me/atst.git").unwrap();
+        assert_eq!(discover_repo(dir.clone(), None).unwrap().owner, "me");
+        repo.remote("mirror", "https://example.com/mirror/atst")
+            .unwrap();
+        assert!(discover_repo(dir.clone(), None).is_err());
+
+        repo.remote("origin", "git@github.com:me/atst.git").unwrap();
+        repo.remote("upstream", "https://github.com/dod-ccpo/atst")
+            .unwrap();
+        assert_eq!(discover_repo(dir.clone(), None).unwrap().owner, "dod-ccpo");
+        assert_eq!(
+            discover_repo(dir.clone(), Some("origin")).unwrap().owner,
+            "me"
+        );
+        assert!(discover_repo(dir.clone(), Some("nope")).is_err());
+
+        let upstream = discover_repo(dir.clone(), None).unwrap();
+        let mine = discover_repo(dir.clone(), Some("origin")).unwrap();
+        assert_eq!(
+            remote_for(dir.clone(), &upstream, None).unwrap(),
+            "upstream"
+        );
+        assert_eq!(remote_for(dir.clone(), &mine, None).unwrap(), "origin");
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    /// Commits `contents` as README.md on top of `parent`, without moving any
+    /// branch.
+    fn commit_readme(repo: &Repository, parent: &git2::Commit, contents: &str) -> Oid {
+        let blob = repo.blob(contents.as_bytes()).unwrap();
+        let mut tree = repo.treebuilder(Some(&parent.tree().unwrap())).unwrap();
+        tree.insert("README.md", blob, 0o100644).unwrap();
+        let tree = repo.find_tree(tree.write().unwrap()).unwrap();
+        let signature = git2::Signature::now("pears", "pears@example.com").unwrap();
+        repo.commit(None, &signature, &signature, contents, &tree, &[parent])
+            .unwrap()
+    }
+
+    #[test]
+    fn conflicting_paths() {
+        let (upstream_dir, upstream) = init_repo("conflicts-upstream");
+        let initial = upstream.head().unwrap().peel_to_commit().unwrap();
+        let base = commit_readme(&upstream, &initial, "pears\nbase\n");
+        upstream
+            .reference("refs/heads/master", base, true, "base")
+            .unwrap();
+        let clean = commit_readme(
+            &upstream,
+            &upstream.find_commit(base).unwrap(),
+            "pears\nbase\n\nmore\n",
+        );
+        upstream
+            .reference("refs/pull/1/head", clean, true, "clean")
+            .unwrap();
+        let conflicting = commit_readme(&upstream, &initial, "pears\nhead\n");
+        upstream
+            .reference("refs/pull/2/head", conflicting, true, "conflicting")
+            .unwrap();
+
+        let (dir, repo) = init_repo("conflicts");
+        repo.remote("origin", upstream_dir.to_str().unwrap())
+            .unwrap();
+        let (base, clean, conflicting) =
+            (base.to_string(), clean.to_string(), conflicting.to_string());
+        assert!(
+            merge_conflicts(dir.clone(), "origin", 1, "master", &base, &clean, "")
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            merge_conflicts(dir.clone(), "origin", 2, "master", &base, &conflicting, "").unwrap(),
+            vec![String::from("README.md")]
+        );
+        let missing = "a".repeat(40);
+        assert!(merge_conflicts(dir.clone(), "origin", 2, "master", &base, &missing, "").is_err());
+        assert!(!has_uncommitted_changes(&repo).unwrap());
+
+        let _ = fs::remove_dir_all(upstream_dir);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn local_branches_of_pull_requests() {
+        let (dir, repo) = init_repo("local");
+        repo.remote("origin", "git@github.com:dod-ccpo/atst.git")
+            .unwrap();
+        let initial = repo.head().unwrap().peel_to_commit().unwrap();
+        let next = commit_readme(&repo, &initial, "pears\nnext\n");
+        let next_commit = repo.find_commit(next).unwrap();
+        let other = commit_readme(&repo, &initial, "pears\nother\n");
+
+        // Behind: checked out as pr/340 before a push.
+        repo.branch("pr/340", &initial, false).unwrap();
+        // Ahead: named after the head branch, with a commit not pushed yet.
+        repo.branch("coverage", &next_commit, false).unwrap();
+        // Up to date: named differently, but tracking the head branch.
+        repo.branch("repr", &next_commit, false).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("branch.repr.remote", "origin").unwrap();
+        config
+            .set_str("branch.repr.merge", "refs/heads/model-repr")
+            .unwrap();
+        // Diverged.
+        repo.branch("circleci-cd", &next_commit, false).unwrap();
+        // From a fork without a remote, so not the pull request's.
+        repo.branch("end-to-end-tests", &initial, false).unwrap();
+
+        let mock_api = GitHubMockAPI::default();
+        let atst = ConfigRepo {
+            owner: String::from("dod-ccpo"),
+            name: String::from("atst"),
+            ..Default::default()
+        };
+        let mut prs = mock_api
+            .list_pull_requests(&Config::default(), &atst, StateFilter::All)
+            .unwrap()
+            .pull_requests
+            .nodes;
+        for pr in prs.iter_mut() {
+            let head = match pr.number {
+                276 => other,
+                350 => initial.id(),
+                _ => next,
+            };
+            pr.head_ref_oid = Some(head.to_string());
+        }
+        let prs: Vec<&PullRequest> = prs.iter().collect();
+
+        let states =
+            local_branch_states(dir.clone(), &atst, &prs, |pr| format!("pr/{}", pr.number))
+                .unwrap();
+        assert_eq!(states.get(&340), Some(&LocalBranchState::Behind(1)));
+        assert_eq!(states.get(&350), Some(&LocalBranchState::Ahead(1)));
+        assert_eq!(states.get(&370), Some(&LocalBranchState::UpToDate));
+        assert_eq!(
+            states.get(&276),
+            Some(&LocalBranchState::Diverged {
+                ahead: 1,
+                behind: 1
+            })
+        );
+        assert_eq!(states.get(&358), None);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn pull_request_references() {
+        let reference = parse_pull_request_reference("340").unwrap();
+        assert_eq!(reference.number, 340);
+        assert!(reference.repo.is_none());
+        assert!(parse_pull_request_reference("#340").unwrap().repo.is_none());
+
+        let reference = parse_pull_request_reference("dod-ccpo/atst#340").unwrap();
+        let repo = reference.repo.unwrap();
+        assert_eq!(
+            (repo.owner.as_str(), repo.name.as_str()),
+            ("dod-ccpo", "atst")
+        );
+        assert_eq!(repo.host, None);
+
+        let reference = parse_pull_request_reference("ghe.example.com/dod-ccpo/atst#340").unwrap();
+        assert_eq!(
+            reference.repo.unwrap().host,
+            Some(String::from("ghe.example.com"))
+        );
+
+        let reference =
+            parse_pull_request_reference("https://github.com/dod-ccpo/atst/pull/340/files")
+                .unwrap();
+        assert_eq!(reference.number, 340);
+        let repo = reference.repo.unwrap();
+        assert_eq!(repo.host, Some(String::from("github.com")));
+        assert_eq!(repo.name, "atst");
+
+        for malformed in &["", "#", "atst#340", "dod-ccpo/atst", "#34o", "99999999999"] {
+            assert!(
+                parse_pull_request_reference(malformed).is_err(),
+                "{}",
+                malformed
+            );
+        }
+    }
+
+    #[test]
+    fn checkout_only_fast_forwards() {
+        let (upstream_dir, upstream) = init_repo("checkout-upstream");
+        let initial = upstream.head().unwrap().peel_to_commit().unwrap();
+        let first = commit_readme(&upstream, &initial, "pears\nfirst\n");
+        upstream
+            .reference("refs/pull/340/head", first, true, "first")
+            .unwrap();
+
+        let (dir, repo) = init_repo("checkout");
+        repo.remote("origin", upstream_dir.to_str().unwrap())
+            .unwrap();
+        let atst = ConfigRepo {
+            owner: String::from("dod-ccpo"),
+            name: String::from("atst"),
+            ..Default::default()
+        };
+        let mut pr = GitHubMockAPI::default()
+            .fetch_pull_request(&Config::default(), &atst, 340)
+            .unwrap();
+        pr.is_cross_repository = true;
+        let checkout = || checkout_pull_request(dir.clone(), "origin", &pr, "pr/340", "");
+
+        checkout().unwrap();
+        assert_eq!(repo.refname_to_id("refs/heads/pr/340").unwrap(), first);
+        assert_eq!(
+            head_branch(dir.clone()).unwrap(),
+            Some(HeadBranch::PullRequest(340))
+        );
+
+        // A push to the pull request fast-forwards the branch.
+        let second = commit_readme(
+            &upstream,
+            &upstream.find_commit(first).unwrap(),
+            "pears\nsecond\n",
+        );
+        upstream
+            .reference("refs/pull/340/head", second, true, "second")
+            .unwrap();
+        checkout().unwrap();
+        assert_eq!(repo.refname_to_id("refs/heads/pr/340").unwrap(), second);
+
+        // A local commit that isn't pushed is kept.
+        let local = commit_readme(&repo, &repo.find_commit(second).unwrap(), "pears\nlocal\n");
+        repo.reset(
+            &repo.find_object(local, None).unwrap(),
+            git2::ResetType::Hard,
+            None,
+        )
+        .unwrap();
+        let error = checkout().unwrap_err();
+        assert!(error.details.starts_with("pr/340 has commits"));
+        assert_eq!(repo.refname_to_id("refs/heads/pr/340").unwrap(), local);
+
+        let _ = fs::remove_dir_all(upstream_dir);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn uncommitted_changes() {
+        let (dir, repo) = init_repo("status");
+        fs::write(dir.join("notes.txt"), "untracked\n").unwrap();
+        assert!(!has_uncommitted_changes(&repo).unwrap());
+        fs::write(dir.join("README.md"), "pears!\n").unwrap();
+        assert!(has_uncommitted_changes(&repo).unwrap());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn head_branch_prefers_upstream() {
+        let (dir, repo) = init_repo("head");
+        let commit = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.branch("pr/340", &commit, false).unwrap();
+        repo.set_head("refs/heads/pr/340").unwrap();
+        assert_eq!(
+            head_branch(dir.clone()).unwrap(),
+            Some(HeadBranch::Named(String::from("pr/340")))
+        );
+
+        let mut config = repo.config().unwrap();
+        config.set_str("branch.pr/340.remote", "origin").unwrap();
+        config
+            .set_str("branch.pr/340.merge", "refs/heads/help-content")
+            .unwrap();
+        assert_eq!(
+            head_branch(dir.clone()).unwrap(),
+            Some(HeadBranch::Named(String::from("help-content")))
+        );
+
+        config
+            .set_str("branch.pr/340.merge", "refs/pull/340/head")
+            .unwrap();
+        assert_eq!(
+            head_branch(dir.clone()).unwrap(),
+            Some(HeadBranch::PullRequest(340))
+        );
+
+        repo.set_head_detached(commit.id()).unwrap();
+        assert_eq!(head_branch(dir.clone()).unwrap(), None);
+
+        let _ = fs::remove_dir_all(dir);
     }
 }
@@ -1,124 +1,740 @@
+use chrono::prelude::*;
 use reqwest;
+use serde::de::DeserializeOwned;
 use serde_json;
+use std::cmp;
+use std::collections::HashMap;
 use std::error::Error;
+use std::sync::Mutex;
+use std::thread;
+use std::time;
 use std::{convert, fmt};
 
-use types::ConfigRepo;
-use types::{Config, GitHubError, GraphqlResponse, Repo};
+use cache::{self, Cache, CacheEntry};
+use pool;
+use types::{
+    ChangedFile, Config, GitHubError, GraphqlError, GraphqlResponse, MergeMethod, NodeResponse,
+    PearsError, PullRequest, PullRequestComments, PullRequestRepository, PullRequestResponse,
+    PullRequestReviewThreads, PullRequestReviews, RateLimit, RateLimitedResponse, Repo,
+    RepoResponse, RepositoryParent, RepositoryParentResponse, ReviewComments, ReviewEvent,
+    SearchResponse, StateFilter, User,
+};
+use types::{ConfigRepo, Paginated};
 
 impl fmt::Display for GitHubError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Could not reach GitHub.")
+        match self {
+            GitHubError::Authentication(details) => {
+                write!(f, "GitHub rejected the token: {}", details)
+            }
+            GitHubError::Forbidden(details) => write!(f, "Access denied by GitHub: {}", details),
+            GitHubError::NotFound(details) => write!(f, "Not found on GitHub: {}", details),
+            GitHubError::InsufficientScopes(details) => {
+                write!(f, "The token is missing required scopes: {}", details)
+            }
+            GitHubError::RateLimited(details) => write!(f, "Rate limited by GitHub: {}", details),
+            GitHubError::Network(details) => write!(f, "Could not reach GitHub: {}", details),
+            GitHubError::MalformedResponse(details) => {
+                write!(f, "Could not understand GitHub's response: {}", details)
+            }
+            GitHubError::NotCached(details) => write!(f, "Offline: {}", details),
+        }
     }
 }
 
 impl Error for GitHubError {
     fn description(&self) -> &str {
-        "Could not reach GitHub."
+        match self {
+            GitHubError::Authentication(_) => "GitHub rejected the token.",
+            GitHubError::Forbidden(_) => "Access denied by GitHub.",
+            GitHubError::NotFound(_) => "Not found on GitHub.",
+            GitHubError::InsufficientScopes(_) => "The token is missing required scopes.",
+            GitHubError::RateLimited(_) => "Rate limited by GitHub.",
+            GitHubError::Network(_) => "Could not reach GitHub.",
+            GitHubError::MalformedResponse(_) => "Could not understand GitHub's response.",
+            GitHubError::NotCached(_) => "Offline and nothing cached.",
+        }
     }
 
-    fn cause(&self) -> Option<&Error> {
+    fn cause(&self) -> Option<&dyn Error> {
         None
     }
 }
 
+impl GitHubError {
+    /// A suggestion for what the user can do about the error.
+    pub fn hint(&self) -> &'static str {
+        match self {
+            GitHubError::Authentication(_) => {
+                "Check that the \"token\" in your config is a valid, unexpired personal access token."
+            }
+            GitHubError::Forbidden(_) => {
+                "If the organisation uses SAML single sign-on, authorize the token for it at https://github.com/settings/tokens."
+            }
+            GitHubError::NotFound(_) => {
+                "Check the repository owner and name, and that your token can see private repositories."
+            }
+            GitHubError::InsufficientScopes(_) => {
+                "Regenerate your token at https://github.com/settings/tokens with the \"repo\" scope, and \"read:org\" to see team review requests."
+            }
+            GitHubError::RateLimited(_) => "Wait a while before trying again.",
+            GitHubError::Network(_) => "Check your network connection.",
+            GitHubError::MalformedResponse(_) => {
+                "GitHub may be having problems. Try again later."
+            }
+            GitHubError::NotCached(_) => "Run the same command without --offline to cache it.",
+        }
+    }
+}
+
 impl convert::From<reqwest::Error> for GitHubError {
     fn from(e: reqwest::Error) -> Self {
-        GitHubError {
-            details: e.description().to_string(),
-        }
+        GitHubError::Network(e.to_string())
     }
 }
 
 impl convert::From<serde_json::Error> for GitHubError {
     fn from(e: serde_json::Error) -> Self {
-        GitHubError {
-            details: e.description().to_string(),
+        GitHubError::MalformedResponse(e.to_string())
+    }
+}
+
+impl convert::From<GitHubError> for PearsError {
+    fn from(e: GitHubError) -> Self {
+        PearsError {
+            details: format!("{}\n{}", e, e.hint()),
         }
     }
 }
 
 pub trait GithubAPI {
-    fn fetch_repo(&self, config: &Config, repo: &ConfigRepo) -> Result<Repo, GitHubError>;
+    /// Lists a repository's pull requests without their comments or review
+    /// discussion.
+    fn list_pull_requests(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        state: StateFilter,
+    ) -> Result<Repo, GitHubError>;
+
+    /// Lists the pull requests of several repositories, passing each to
+    /// `on_repo` in the order given, as soon as it and those before it are in.
+    /// A repository that can't be listed, e.g. one renamed or deleted, is
+    /// passed its own error without holding up the others.
+    fn fetch_repos<F>(
+        &self,
+        config: &Config,
+        repos: &[ConfigRepo],
+        state: StateFilter,
+        mut on_repo: F,
+    ) -> Result<(), GitHubError>
+    where
+        F: FnMut(&ConfigRepo, Result<Repo, GitHubError>),
+    {
+        for repo in repos {
+            on_repo(repo, self.list_pull_requests(config, repo, state));
+        }
+        Ok(())
+    }
+
+    /// Fetches a single pull request with all of its discussion, whatever its
+    /// state. Fails with `GitHubError::NotFound` if there is no such PR.
+    fn fetch_pull_request(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        number: i32,
+    ) -> Result<PullRequest, GitHubError>;
+
+    fn fetch_rate_limit(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+    ) -> Result<RateLimit, GitHubError>;
+
+    /// Searches pull requests on the repo's host with GitHub's search syntax,
+    /// across every repository the token can see.
+    fn search_pull_requests(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        query: &str,
+    ) -> Result<Vec<PullRequest>, GitHubError>;
+
+    /// The repository `repo` was forked from, or `None` if it isn't a fork.
+    fn fetch_parent(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+    ) -> Result<Option<ConfigRepo>, GitHubError>;
+
+    /// Lists the pull requests, in any state, whose head is a branch named
+    /// `branch`, newest first. Pull requests from forks are included.
+    fn list_branch_pull_requests(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        branch: &str,
+    ) -> Result<Vec<PullRequest>, GitHubError>;
+
+    /// Converts a pull request to a draft, or marks it ready for review, and
+    /// returns whether it is a draft afterwards. Doing either twice is harmless.
+    fn set_draft(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        pull_request_id: &str,
+        draft: bool,
+    ) -> Result<bool, GitHubError>;
+
+    /// Merges a pull request, unless its head is no longer `expected_head_oid`.
+    fn merge_pull_request(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        pull_request_id: &str,
+        method: MergeMethod,
+        expected_head_oid: Option<&str>,
+    ) -> Result<(), GitHubError>;
+
+    fn delete_ref(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        ref_id: &str,
+    ) -> Result<(), GitHubError>;
+
+    /// Submits a review. `body` may only be empty when approving.
+    fn add_review(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        pull_request_id: &str,
+        event: ReviewEvent,
+        body: &str,
+    ) -> Result<(), GitHubError>;
+
+    /// Lists the files a pull request changes, with their patches.
+    fn fetch_changed_files(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        number: i32,
+    ) -> Result<Vec<ChangedFile>, GitHubError>;
+
+    /// The rate limit as of the last query made against the repo's endpoint.
+    fn rate_limit(&self, _config: &Config, _repo: &ConfigRepo) -> Option<RateLimit> {
+        None
+    }
+
+    /// When the oldest cached data returned so far was fetched, if any was.
+    fn data_as_of(&self) -> Option<DateTime<Utc>> {
+        None
+    }
+}
+
+/// Number of repositories fetched at once when the config doesn't say.
+const DEFAULT_CONCURRENCY: usize = 4;
+
+fn concurrency(config: &Config) -> usize {
+    config.concurrency.unwrap_or(DEFAULT_CONCURRENCY)
+}
+
+/// Looks for pull request `number` in each of `repos`, several at a time, and
+/// returns the repo and PR from the first repo in order that has it.
+pub fn find_pull_request<'a, T>(
+    config: &Config,
+    repos: &'a [ConfigRepo],
+    api: &T,
+    number: i32,
+) -> Result<Option<(&'a ConfigRepo, PullRequest)>, GitHubError>
+where
+    T: GithubAPI + Sync,
+{
+    let mut found = None;
+    pool::in_order(
+        repos,
+        concurrency(config),
+        |repo| match api.fetch_pull_request(config, repo, number) {
+            Ok(pr) => Ok(Some(pr)),
+            Err(GitHubError::NotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        },
+        |repo, pr| {
+            found = pr.map(|pr| (repo, pr));
+            Ok(found.is_none())
+        },
+    )?;
+    Ok(found)
+}
+
+/// Finds the pull request for a branch, preferring open pull requests and then
+/// those not from a fork, since those are the ones being worked on.
+pub fn find_branch_pull_request<T: GithubAPI>(
+    config: &Config,
+    repo: &ConfigRepo,
+    api: &T,
+    branch: &str,
+) -> Result<Option<PullRequest>, GitHubError> {
+    let mut prs = api.list_branch_pull_requests(config, repo, branch)?;
+    // Stable, so that the newest comes first among equals.
+    prs.sort_by_key(|pr| (pr.state != "OPEN", pr.is_cross_repository));
+    Ok(prs.into_iter().next())
+}
+
+/// The parent of a fork, on the same host as the fork.
+fn parent_repo(fork: &ConfigRepo, repository: RepositoryParent) -> Option<ConfigRepo> {
+    if !repository.is_fork {
+        return None;
+    }
+    repository.parent.map(|parent| ConfigRepo {
+        owner: parent.owner.login,
+        name: parent.name,
+        ..fork.clone()
+    })
+}
+
+/// Maps an entry of a GraphQL response's `errors` array onto a `GitHubError`.
+fn graphql_error(error: GraphqlError) -> GitHubError {
+    match error.kind.as_deref() {
+        Some("NOT_FOUND") => GitHubError::NotFound(error.message),
+        Some("FORBIDDEN") => GitHubError::Forbidden(error.message),
+        Some("INSUFFICIENT_SCOPES") => GitHubError::InsufficientScopes(error.message),
+        Some("RATE_LIMITED") => GitHubError::RateLimited(error.message),
+        _ => GitHubError::MalformedResponse(error.message),
+    }
 }
 
-fn parse_repo_response(repo_response: String) -> Result<Repo, serde_json::Error> {
-    let resp: GraphqlResponse = serde_json::from_str(&repo_response)?;
-    Ok(resp.data.repository)
+/// Whether a response's headers say the rate limit is used up, which GitHub
+/// answers with a 403 as it does for resources the token may not access.
+fn rate_limit_exhausted(headers: &reqwest::header::HeaderMap) -> bool {
+    headers
+        .get("X-RateLimit-Remaining")
+        .map_or(false, |h| h.as_bytes() == b"0")
+        || headers.contains_key("Retry-After")
 }
 
-pub struct GitHubGraphqlAPI {}
+/// Maps a non-success HTTP status onto a `GitHubError`. GitHub explains most
+/// failures with a `{"message": ...}` body, which is used when present.
+/// `rate_limited` is whether the response's headers say the rate limit is used
+/// up.
+fn http_error(status: u16, rate_limited: bool, response: &str) -> GitHubError {
+    #[derive(Deserialize)]
+    struct ErrorBody {
+        message: String,
+    }
+
+    let message = serde_json::from_str::<ErrorBody>(response)
+        .map(|b| b.message)
+        .unwrap_or_else(|_| format!("HTTP {}", status));
+
+    match status {
+        401 => GitHubError::Authentication(message),
+        403 if rate_limited => GitHubError::RateLimited(message),
+        403 => GitHubError::Forbidden(message),
+        404 => GitHubError::NotFound(message),
+        429 => GitHubError::RateLimited(message),
+        500..=599 => GitHubError::Network(message),
+        _ => GitHubError::MalformedResponse(message),
+    }
+}
+
+/// Whether `error` only means a team review request couldn't be read, which
+/// needs the `read:org` scope. GitHub leaves such a `requestedReviewer` null.
+fn is_team_scope_error(error: &GraphqlError) -> bool {
+    error.kind.as_deref() == Some("INSUFFICIENT_SCOPES")
+        && error.path.iter().any(|p| p == "requestedReviewer")
+}
+
+/// Parses the body of a successful GraphQL response that may have data for
+/// some fields and errors for others. Errors for a field are returned with the
+/// data; any other error fails the whole response.
+fn parse_partial_response<T: DeserializeOwned>(
+    response: String,
+) -> Result<(T, Vec<GraphqlError>), GitHubError> {
+    let resp: GraphqlResponse = serde_json::from_str(&response)?;
+    let data = match resp.data {
+        Some(data) => data,
+        None => {
+            return Err(match resp.errors.into_iter().next() {
+                Some(error) => graphql_error(error),
+                None => GitHubError::MalformedResponse(String::from(
+                    "Response contained neither data nor errors.",
+                )),
+            })
+        }
+    };
+    let mut errors = vec![];
+    for error in resp.errors {
+        if error.path.is_empty() {
+            return Err(graphql_error(error));
+        }
+        if !is_team_scope_error(&error) {
+            errors.push(error);
+        }
+    }
+    match serde_json::from_value(data) {
+        Ok(data) => Ok((data, errors)),
+        // A field left null for its error may not fit `T`.
+        Err(e) => Err(match errors.into_iter().next() {
+            Some(error) => graphql_error(error),
+            None => e.into(),
+        }),
+    }
+}
+
+/// Parses the body of a successful GraphQL response, failing on any error.
+fn parse_response<T: DeserializeOwned>(response: String) -> Result<T, GitHubError> {
+    let (data, errors) = parse_partial_response(response)?;
+    match errors.into_iter().next() {
+        Some(error) => Err(graphql_error(error)),
+        None => Ok(data),
+    }
+}
+
+/// Matches the repos of a batch query with what the response has for their
+/// aliases, `r0` to `r<count - 1>`, and the errors for them.
+fn batch_repos(
+    mut response: HashMap<String, Option<Repo>>,
+    mut errors: Vec<GraphqlError>,
+    count: usize,
+) -> Result<Vec<Result<Repo, GitHubError>>, GitHubError> {
+    let repos = (0..count)
+        .map(|index| {
+            let alias = format!("r{}", index);
+            let error = errors
+                .iter()
+                .position(|e| e.path.first().map_or(false, |p| p == alias.as_str()))
+                .map(|i| errors.remove(i));
+            match (error, response.remove(&alias).and_then(|r| r)) {
+                (Some(error), _) => Err(graphql_error(error)),
+                (None, Some(repository)) => Ok(repository),
+                (None, None) => Err(GitHubError::MalformedResponse(format!(
+                    "Response is missing {}.",
+                    alias
+                ))),
+            }
+        })
+        .collect();
+    match errors.into_iter().next() {
+        Some(error) => Err(graphql_error(error)),
+        None => Ok(repos),
+    }
+}
 
 #[allow(dead_code)]
-pub struct GitHubMockAPI {}
+fn parse_repo_response(repo_response: String) -> Result<Repo, GitHubError> {
+    let resp: RepoResponse = parse_response(repo_response)?;
+    Ok(resp.repository)
+}
 
-impl GithubAPI for GitHubGraphqlAPI {
-    fn fetch_repo(&self, config: &Config, repo: &ConfigRepo) -> Result<Repo, GitHubError> {
-        let query = r###"
-        query fetchPullRequests($repo_owner: String!, $repo_name: String!) {
+/// Number of pull requests requested per page when listing, GitHub's maximum.
+const PULL_REQUEST_PAGE_SIZE: usize = 100;
+
+/// Number of changed files requested per page, the REST API's maximum.
+const CHANGED_FILES_PAGE_SIZE: usize = 100;
+
+/// The REST API lists no more than 3000 files for a pull request.
+const MAX_CHANGED_FILES_PAGES: usize = 30;
+
+const PULL_REQUESTS_QUERY: &str = r###"
+query fetchPullRequests($repo_owner: String!, $repo_name: String!, $page_size: Int!, $cursor: String, $states: [PullRequestState!]) {
+  rateLimit {
+    limit
+    cost
+    remaining
+    resetAt
+  }
   repository(owner: $repo_owner, name: $repo_name) {
+    ...repositoryFields
+  }
+}
+"###;
+
+/// Spreads `listedPullRequestFields`, so must be sent along with
+/// `LISTED_PULL_REQUEST_FRAGMENT`. Uses the `$page_size`, `$cursor` and
+/// `$states` variables, which every query spreading it must declare.
+const REPOSITORY_FRAGMENT: &str = r###"
+fragment repositoryFields on Repository {
+  pullRequests(first: $page_size, after: $cursor, states: $states, orderBy: {field: UPDATED_AT, direction: DESC}) {
+    pageInfo {
+      hasNextPage
+      endCursor
+    }
+    edges {
+      node {
+        ...listedPullRequestFields
+      }
+    }
+  }
+}
+"###;
+
+/// What listing needs of a pull request, leaving out its body and
+/// discussion. Spreads `reviewRequestFields`, so must be sent along with
+/// `REVIEW_REQUEST_FRAGMENT`.
+const LISTED_PULL_REQUEST_FRAGMENT: &str = r###"
+fragment listedPullRequestFields on PullRequest {
+  id
+  state
+  title
+  number
+  url
+  updatedAt
+  closedAt
+  mergedAt
+  mergeable
+  isDraft
+  headRefName
+  headRefOid
+  isCrossRepository
+  headRepository {
     name
-    pullRequests(last: 20, states: [OPEN]) {
-      edges {
-        node {
-          id
-          state
-          title
-          body
-          number
-          url
-          createdAt
-          updatedAt
-          closedAt
-          mergedAt
-          mergeable
-          author {
-            login
-          }
-          labels(first: 100) {
-            edges {
-              node {
-                id
-                name
-              }
-            }
+    owner {
+      login
+    }
+  }
+  author {
+    login
+  }
+  labels(first: 100) {
+    edges {
+      node {
+        id
+        name
+      }
+    }
+  }
+  reviewDecision
+  reviews(last: 100) {
+    edges {
+      node {
+        author {
+          login
+        }
+        state
+        createdAt
+      }
+    }
+  }
+  reviewRequests(first: 100) {
+    edges {
+      node {
+        ...reviewRequestFields
+      }
+    }
+  }
+  commits(last: 1) {
+    edges {
+      node {
+        commit {
+          statusCheckRollup {
+            state
           }
-          comments(last: 100) {
-            edges {
-              node {
-                id
-                bodyText
-                author {
-                  login
-                }
-                createdAt
-                updatedAt
-              }
+        }
+      }
+    }
+  }
+}
+"###;
+
+const SEARCH_QUERY: &str = r###"
+query searchPullRequests($query: String!, $page_size: Int!, $cursor: String) {
+  rateLimit {
+    limit
+    cost
+    remaining
+    resetAt
+  }
+  search(query: $query, type: ISSUE, first: $page_size, after: $cursor) {
+    pageInfo {
+      hasNextPage
+      endCursor
+    }
+    edges {
+      node {
+        ... on PullRequest {
+          repository {
+            name
+            owner {
+              login
             }
           }
-          reviews(last: 100) {
-            edges {
-              node {
-                id
-                author {
-                  login
-                }
-                createdAt
-                updatedAt
-                bodyText
-                state
-                comments(last: 100) {
-                  edges {
-                    node {
-                      author {
-                        login
-                      }
-                      id
-                      bodyText
-                      diffHunk
-                      createdAt
-                      updatedAt
-                    }
+          ...listedPullRequestFields
+        }
+      }
+    }
+  }
+}
+"###;
+
+const REPOSITORY_PARENT_QUERY: &str = r###"
+query fetchRepositoryParent($repo_owner: String!, $repo_name: String!) {
+  rateLimit {
+    limit
+    cost
+    remaining
+    resetAt
+  }
+  repository(owner: $repo_owner, name: $repo_name) {
+    isFork
+    parent {
+      name
+      owner {
+        login
+      }
+    }
+  }
+}
+"###;
+
+/// Spreads `listedPullRequestFields`, so must be sent along with
+/// `SEARCH_FRAGMENTS`.
+const BRANCH_PULL_REQUESTS_QUERY: &str = r###"
+query fetchBranchPullRequests($repo_owner: String!, $repo_name: String!, $branch: String!, $page_size: Int!) {
+  rateLimit {
+    limit
+    cost
+    remaining
+    resetAt
+  }
+  repository(owner: $repo_owner, name: $repo_name) {
+    name
+    pullRequests(headRefName: $branch, first: $page_size, orderBy: {field: CREATED_AT, direction: DESC}) {
+      pageInfo {
+        hasNextPage
+        endCursor
+      }
+      edges {
+        node {
+          ...listedPullRequestFields
+        }
+      }
+    }
+  }
+}
+"###;
+
+/// Pull requests fetched for a branch. More than a few means the branch name is
+/// being reused, and the newest are the ones that matter.
+const BRANCH_PULL_REQUESTS_PAGE_SIZE: usize = 10;
+
+const PULL_REQUEST_QUERY: &str = r###"
+query fetchPullRequest($repo_owner: String!, $repo_name: String!, $number: Int!) {
+  rateLimit {
+    limit
+    cost
+    remaining
+    resetAt
+  }
+  repository(owner: $repo_owner, name: $repo_name) {
+    pullRequest(number: $number) {
+      ...pullRequestFields
+    }
+  }
+}
+"###;
+
+const PULL_REQUEST_FRAGMENT: &str = r###"
+fragment pullRequestFields on PullRequest {
+  id
+  state
+  title
+  body
+  number
+  url
+  updatedAt
+  closedAt
+  mergedAt
+  mergeable
+  isDraft
+  headRefName
+  isCrossRepository
+  headRepository {
+    name
+    owner {
+      login
+    }
+  }
+  headRefOid
+  headRef {
+    id
+  }
+  baseRefName
+  baseRefOid
+  author {
+    login
+  }
+  labels(first: 100) {
+    edges {
+      node {
+        id
+        name
+      }
+    }
+  }
+  comments(first: 100) {
+    pageInfo {
+      hasNextPage
+      endCursor
+    }
+    edges {
+      node {
+        ...commentFields
+      }
+    }
+  }
+  reviewDecision
+  reviews(first: 100) {
+    pageInfo {
+      hasNextPage
+      endCursor
+    }
+    edges {
+      node {
+        ...reviewFields
+      }
+    }
+  }
+  reviewThreads(first: 100) {
+    pageInfo {
+      hasNextPage
+      endCursor
+    }
+    edges {
+      node {
+        ...reviewThreadFields
+      }
+    }
+  }
+  reviewRequests(first: 100) {
+    edges {
+      node {
+        ...reviewRequestFields
+      }
+    }
+  }
+  commits(last: 1) {
+    edges {
+      node {
+        commit {
+          statusCheckRollup {
+            state
+            contexts(first: 100) {
+              edges {
+                node {
+                  __typename
+                  ... on CheckRun {
+                    name
+                    status
+                    conclusion
+                    detailsUrl
+                  }
+                  ... on StatusContext {
+                    context
+                    state
+                    description
+                    targetUrl
                   }
                 }
               }
@@ -129,33 +745,1069 @@ impl GithubAPI for GitHubGraphqlAPI {
     }
   }
 }
-        "###;
+"###;
+
+/// Number of nodes a pull request can cost in `repositoryFields`: itself, its
+/// labels, its reviews, its review requests and its head commit.
+const NODES_PER_PULL_REQUEST: usize = 1 + 100 + 100 + 100 + 1;
+
+/// GitHub refuses queries that could return more nodes than this.
+const MAX_QUERY_NODES: usize = 500_000;
+
+const PULL_REQUEST_COMMENTS_QUERY: &str = r###"
+query fetchPullRequestComments($id: ID!, $cursor: String) {
+  rateLimit {
+    limit
+    cost
+    remaining
+    resetAt
+  }
+  node(id: $id) {
+    ... on PullRequest {
+      comments(first: 100, after: $cursor) {
+        pageInfo {
+          hasNextPage
+          endCursor
+        }
+        edges {
+          node {
+            ...commentFields
+          }
+        }
+      }
+    }
+  }
+}
+"###;
+
+const PULL_REQUEST_REVIEWS_QUERY: &str = r###"
+query fetchPullRequestReviews($id: ID!, $cursor: String) {
+  rateLimit {
+    limit
+    cost
+    remaining
+    resetAt
+  }
+  node(id: $id) {
+    ... on PullRequest {
+      reviews(first: 100, after: $cursor) {
+        pageInfo {
+          hasNextPage
+          endCursor
+        }
+        edges {
+          node {
+            ...reviewFields
+          }
+        }
+      }
+    }
+  }
+}
+"###;
+
+const PULL_REQUEST_REVIEW_THREADS_QUERY: &str = r###"
+query fetchPullRequestReviewThreads($id: ID!, $cursor: String) {
+  rateLimit {
+    limit
+    cost
+    remaining
+    resetAt
+  }
+  node(id: $id) {
+    ... on PullRequest {
+      reviewThreads(first: 100, after: $cursor) {
+        pageInfo {
+          hasNextPage
+          endCursor
+        }
+        edges {
+          node {
+            ...reviewThreadFields
+          }
+        }
+      }
+    }
+  }
+}
+"###;
+
+const REVIEW_THREAD_COMMENTS_QUERY: &str = r###"
+query fetchReviewThreadComments($id: ID!, $cursor: String) {
+  rateLimit {
+    limit
+    cost
+    remaining
+    resetAt
+  }
+  node(id: $id) {
+    ... on PullRequestReviewThread {
+      comments(first: 100, after: $cursor) {
+        pageInfo {
+          hasNextPage
+          endCursor
+        }
+        edges {
+          node {
+            ...reviewCommentFields
+          }
+        }
+      }
+    }
+  }
+}
+"###;
+
+const RATE_LIMIT_QUERY: &str = r###"
+query fetchRateLimit {
+  rateLimit {
+    limit
+    cost
+    remaining
+    resetAt
+  }
+}
+"###;
+
+const CONVERT_TO_DRAFT_MUTATION: &str = r###"
+mutation convertPullRequestToDraft($id: ID!) {
+  convertPullRequestToDraft(input: {pullRequestId: $id}) {
+    pullRequest {
+      isDraft
+    }
+  }
+}
+"###;
+
+const MARK_READY_FOR_REVIEW_MUTATION: &str = r###"
+mutation markPullRequestReadyForReview($id: ID!) {
+  markPullRequestReadyForReview(input: {pullRequestId: $id}) {
+    pullRequest {
+      isDraft
+    }
+  }
+}
+"###;
+
+const ADD_REVIEW_MUTATION: &str = r###"
+mutation addPullRequestReview($id: ID!, $event: PullRequestReviewEvent!, $body: String) {
+  addPullRequestReview(input: {pullRequestId: $id, event: $event, body: $body}) {
+    pullRequestReview {
+      id
+    }
+  }
+}
+"###;
+
+const MERGE_MUTATION: &str = r###"
+mutation mergePullRequest($id: ID!, $method: PullRequestMergeMethod!, $head: GitObjectID) {
+  mergePullRequest(input: {pullRequestId: $id, mergeMethod: $method, expectedHeadOid: $head}) {
+    pullRequest {
+      state
+    }
+  }
+}
+"###;
+
+const DELETE_REF_MUTATION: &str = r###"
+mutation deleteRef($id: ID!) {
+  deleteRef(input: {refId: $id}) {
+    clientMutationId
+  }
+}
+"###;
+
+const COMMENT_FRAGMENT: &str = r###"
+fragment commentFields on IssueComment {
+  id
+  bodyText
+  author {
+    login
+  }
+  createdAt
+  updatedAt
+}
+"###;
+
+const REVIEW_COMMENT_FRAGMENT: &str = r###"
+fragment reviewCommentFields on PullRequestReviewComment {
+  id
+  author {
+    login
+  }
+  bodyText
+  diffHunk
+  createdAt
+  updatedAt
+}
+"###;
+
+/// A review's own comments on the diff are left out, since they show up in
+/// its review threads.
+const REVIEW_FRAGMENT: &str = r###"
+fragment reviewFields on PullRequestReview {
+  author {
+    login
+  }
+  createdAt
+  bodyText
+  state
+}
+"###;
+
+/// Spreads `reviewCommentFields`, so must be sent along with
+/// `REVIEW_COMMENT_FRAGMENT`.
+const REVIEW_THREAD_FRAGMENT: &str = r###"
+fragment reviewThreadFields on PullRequestReviewThread {
+  id
+  path
+  line
+  isResolved
+  isOutdated
+  comments(first: 100) {
+    pageInfo {
+      hasNextPage
+      endCursor
+    }
+    edges {
+      node {
+        ...reviewCommentFields
+      }
+    }
+  }
+}
+"###;
+
+const REVIEW_REQUEST_FRAGMENT: &str = r###"
+fragment reviewRequestFields on ReviewRequest {
+  requestedReviewer {
+    __typename
+    ... on User {
+      login
+    }
+    ... on Team {
+      slug
+      combinedSlug
+    }
+  }
+}
+"###;
+
+/// Every fragment `repositoryFields` needs, itself included.
+const REPOSITORY_FRAGMENTS: &[&str] = &[
+    REPOSITORY_FRAGMENT,
+    LISTED_PULL_REQUEST_FRAGMENT,
+    REVIEW_REQUEST_FRAGMENT,
+];
+
+const SEARCH_FRAGMENTS: &[&str] = &[LISTED_PULL_REQUEST_FRAGMENT, REVIEW_REQUEST_FRAGMENT];
+
+/// Every fragment `pullRequestFields` needs, itself included.
+const PULL_REQUEST_FRAGMENTS: &[&str] = &[
+    PULL_REQUEST_FRAGMENT,
+    COMMENT_FRAGMENT,
+    REVIEW_FRAGMENT,
+    REVIEW_COMMENT_FRAGMENT,
+    REVIEW_THREAD_FRAGMENT,
+    REVIEW_REQUEST_FRAGMENT,
+];
+
+/// How many pull requests to ask for when `fetched` have been fetched so far.
+fn page_size(config: &Config, fetched: usize) -> usize {
+    match config.limit {
+        Some(limit) => cmp::min(PULL_REQUEST_PAGE_SIZE, limit.saturating_sub(fetched)),
+        None => PULL_REQUEST_PAGE_SIZE,
+    }
+}
+
+/// Template for `batch_query`, which fills in a declaration and an aliased
+/// selection per repository.
+const BATCH_QUERY: &str = r###"
+query fetchRepositories($page_size: Int!, $cursor: String, $states: [PullRequestState!]{declarations}) {
+  rateLimit {
+    limit
+    cost
+    remaining
+    resetAt
+  }
+{selections}}
+"###;
+
+/// Builds a query fetching the first page of each repo, aliased `r0`, `r1`,
+/// and so on, along with its variables.
+fn batch_query(
+    repos: &[&ConfigRepo],
+    page_size: usize,
+    state: StateFilter,
+) -> (String, serde_json::Value) {
+    let mut declarations = String::new();
+    let mut selections = String::new();
+    let mut variables = serde_json::Map::new();
+    variables.insert(String::from("page_size"), json!(page_size));
+    variables.insert(String::from("cursor"), serde_json::Value::Null);
+    variables.insert(String::from("states"), json!(state.graphql_states()));
+
+    for (i, repo) in repos.iter().enumerate() {
+        declarations.push_str(&format!(", $owner{}: String!, $name{}: String!", i, i));
+        selections.push_str(&format!(
+            "  r{}: repository(owner: $owner{}, name: $name{}) {{\n    ...repositoryFields\n  }}\n",
+            i, i, i
+        ));
+        variables.insert(format!("owner{}", i), json!(repo.owner));
+        variables.insert(format!("name{}", i), json!(repo.name));
+    }
+
+    let query = BATCH_QUERY
+        .replace("{declarations}", &declarations)
+        .replace("{selections}", &selections);
+    (query, serde_json::Value::Object(variables))
+}
+
+/// Joins a query with the fragments it spreads. GitHub rejects documents with
+/// unused fragments, so `fragments` must only contain ones `query` needs.
+fn with_fragments(query: &str, fragments: &[&str]) -> String {
+    let mut document = String::from(query);
+    for fragment in fragments {
+        document.push_str(fragment);
+    }
+    document
+}
+
+/// The least time to wait out a rate limit, for when GitHub's reset time has
+/// already passed but it still refuses.
+const MIN_RATE_LIMIT_WAIT: time::Duration = time::Duration::from_secs(10);
+
+/// How many times a query is retried after being rate limited.
+const RATE_LIMIT_RETRIES: usize = 3;
+
+/// How long to wait at `now` for a rate limit that resets at `reset_at`.
+fn reset_wait(reset_at: DateTime<Utc>, now: DateTime<Utc>) -> time::Duration {
+    (reset_at - now)
+        .to_std()
+        .map_or(MIN_RATE_LIMIT_WAIT, |d| cmp::max(d, MIN_RATE_LIMIT_WAIT))
+}
+
+/// Sleeps until `reset_at` if the config allows waiting out the rate limit,
+/// otherwise fails with `error`.
+fn wait_for_reset(
+    config: &Config,
+    reset_at: DateTime<Utc>,
+    error: GitHubError,
+) -> Result<(), GitHubError> {
+    if !config.rate_limit_wait.unwrap_or(false) {
+        return Err(error);
+    }
+    thread::sleep(reset_wait(reset_at, Utc::now()));
+    Ok(())
+}
+
+pub struct GitHubGraphqlAPI {
+    cache: Cache,
+
+    /// The last rate limit reported by each API URL queried.
+    rate_limits: Mutex<HashMap<String, RateLimit>>,
+}
+
+/// A change the mock was asked to make, kept so tests can check what would
+/// have been sent to GitHub.
+#[allow(dead_code)]
+#[derive(Debug, PartialEq)]
+pub enum Mutation {
+    Merge {
+        pull_request_id: String,
+        method: MergeMethod,
+        expected_head_oid: Option<String>,
+    },
+    DeleteRef {
+        ref_id: String,
+    },
+    SetDraft {
+        pull_request_id: String,
+        draft: bool,
+    },
+    AddReview {
+        pull_request_id: String,
+        event: ReviewEvent,
+        body: String,
+    },
+}
+
+#[allow(dead_code)]
+#[derive(Default)]
+pub struct GitHubMockAPI {
+    pub mutations: Mutex<Vec<Mutation>>,
+}
+
+impl GitHubGraphqlAPI {
+    pub fn new(cache: Cache) -> GitHubGraphqlAPI {
+        GitHubGraphqlAPI {
+            cache,
+            rate_limits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn query<T: DeserializeOwned>(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        query: String,
+        variables: serde_json::Value,
+    ) -> Result<T, GitHubError> {
+        self.run_query(config, repo, query, variables, true)
+    }
+
+    /// Sends a query, going through the cache if `use_cache` is set, and waiting
+    /// out the rate limit if configured to.
+    fn run_query<T: DeserializeOwned>(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        query: String,
+        variables: serde_json::Value,
+        use_cache: bool,
+    ) -> Result<T, GitHubError> {
+        let (data, errors) = self.run_partial_query(config, repo, query, variables, use_cache)?;
+        match errors.into_iter().next() {
+            Some(error) => Err(graphql_error(error)),
+            None => Ok(data),
+        }
+    }
+
+    /// Like `run_query`, but returns errors for single fields along with the
+    /// data for the others, as `parse_partial_response` does.
+    fn run_partial_query<T: DeserializeOwned>(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        query: String,
+        variables: serde_json::Value,
+        use_cache: bool,
+    ) -> Result<(T, Vec<GraphqlError>), GitHubError> {
         let body = json!({
            "query": query,
-           "variables": {
-               "repo_owner": repo.owner,
-               "repo_name": repo.name,
-           }
+           "variables": variables,
         })
         .to_string();
-        let mut response = reqwest::Client::new()
-            .post("https://api.github.com/graphql")
-            .bearer_auth(config.token.to_owned())
-            .body(body)
-            .send()?;
-        let response_body = response.text()?;
-        let repository = parse_repo_response(response_body)?;
+        let api_url = config.api_url_for(repo);
+
+        let key = cache::key(&api_url, &body);
+        let cached = if use_cache {
+            self.cache.load(&key)
+        } else {
+            None
+        };
+        if let Some(ref entry) = cached {
+            if self.cache.is_fresh(entry) {
+                let (response, errors) =
+                    parse_partial_response::<RateLimitedResponse<T>>(self.cache.serve(entry))?;
+                return Ok((response.data, errors));
+            }
+        }
+        if self.cache.is_offline() {
+            return Err(GitHubError::NotCached(if use_cache {
+                format!("Nothing cached for {}/{}.", repo.owner, repo.name)
+            } else {
+                String::from("This is never cached.")
+            }));
+        }
+
+        let mut retries = 0;
+        loop {
+            let exhausted = self
+                .rate_limits
+                .lock()
+                .unwrap()
+                .get(&api_url)
+                .filter(|r| r.remaining <= 0 && r.reset_at > Utc::now())
+                .map(|r| r.reset_at);
+            if let Some(reset_at) = exhausted {
+                wait_for_reset(
+                    config,
+                    reset_at,
+                    GitHubError::RateLimited(format!("No requests left until {}.", reset_at)),
+                )?;
+            }
+
+            let mut request = reqwest::Client::new()
+                .post(api_url.as_str())
+                .bearer_auth(config.token_for(repo).to_owned())
+                .body(body.clone());
+            if let Some(etag) = cached.as_ref().and_then(|e| e.etag.as_ref()) {
+                request = request.header("If-None-Match", etag.as_str());
+            }
+            let mut response = request.send()?;
+            let status = response.status().as_u16();
+            let etag = response
+                .headers()
+                .get("ETag")
+                .and_then(|h| h.to_str().ok())
+                .map(String::from);
+            let reset_at = response
+                .headers()
+                .get("X-RateLimit-Reset")
+                .and_then(|h| h.to_str().ok())
+                .and_then(|h| h.parse::<i64>().ok())
+                .map(|t| Utc.timestamp(t, 0));
+            let rate_limited = rate_limit_exhausted(response.headers());
+            let response_body = response.text()?;
+
+            // Not modified since it was cached, so it's as good as new.
+            if let (304, Some(entry)) = (status, cached.as_ref()) {
+                let entry = CacheEntry {
+                    fetched_at: Utc::now(),
+                    etag: etag.or_else(|| entry.etag.clone()),
+                    body: entry.body.clone(),
+                };
+                self.cache.store(&key, &entry);
+                let (response, errors) =
+                    parse_partial_response::<RateLimitedResponse<T>>(entry.body)?;
+                return Ok((response.data, errors));
+            }
+
+            let parsed = if (200..300).contains(&status) {
+                parse_partial_response::<RateLimitedResponse<T>>(response_body.clone())
+            } else {
+                Err(http_error(status, rate_limited, &response_body))
+            };
+            match parsed {
+                Ok((response, errors)) => {
+                    if use_cache {
+                        self.cache.store(
+                            &key,
+                            &CacheEntry {
+                                fetched_at: Utc::now(),
+                                etag,
+                                body: response_body,
+                            },
+                        );
+                    }
+                    if let Some(rate_limit) = response.rate_limit {
+                        self.rate_limits.lock().unwrap().insert(api_url, rate_limit);
+                    }
+                    return Ok((response.data, errors));
+                }
+                Err(GitHubError::RateLimited(message)) => match reset_at {
+                    Some(reset_at) if retries < RATE_LIMIT_RETRIES => {
+                        retries += 1;
+                        wait_for_reset(config, reset_at, GitHubError::RateLimited(message))?
+                    }
+                    _ => return Err(GitHubError::RateLimited(message)),
+                },
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// GETs `path` from the REST API on the repo's host, for the little that
+    /// GraphQL doesn't expose. Cached like queries are.
+    fn rest_get<T: DeserializeOwned>(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        path: &str,
+    ) -> Result<T, GitHubError> {
+        let url = format!("{}{}", config.rest_url_for(repo), path);
+        let key = cache::key(&url, "");
+        let cached = self.cache.load(&key);
+        if let Some(ref entry) = cached {
+            if self.cache.is_fresh(entry) {
+                return Ok(serde_json::from_str(&self.cache.serve(entry))?);
+            }
+        }
+        if self.cache.is_offline() {
+            return Err(GitHubError::NotCached(format!(
+                "Nothing cached for {}.",
+                url
+            )));
+        }
+
+        let mut request = reqwest::Client::new()
+            .get(url.as_str())
+            .bearer_auth(config.token_for(repo).to_owned())
+            .header("Accept", "application/vnd.github.v3+json");
+        if let Some(etag) = cached.as_ref().and_then(|e| e.etag.as_ref()) {
+            request = request.header("If-None-Match", etag.as_str());
+        }
+        let mut response = request.send()?;
+        let status = response.status().as_u16();
+        let etag = response
+            .headers()
+            .get("ETag")
+            .and_then(|h| h.to_str().ok())
+            .map(String::from);
+        let rate_limited = rate_limit_exhausted(response.headers());
+        let body = match (status, cached) {
+            (304, Some(entry)) => entry.body,
+            (200..=299, _) => response.text()?,
+            _ => return Err(http_error(status, rate_limited, &response.text()?)),
+        };
+
+        let parsed = serde_json::from_str(&body)?;
+        self.cache.store(
+            &key,
+            &CacheEntry {
+                fetched_at: Utc::now(),
+                etag,
+                body,
+            },
+        );
+        Ok(parsed)
+    }
+
+    /// Fetches several repositories in one query by aliasing each as `r<n>`.
+    /// `chunk` indexes into `repos`, all of which must share an endpoint. A repo
+    /// the query can't resolve gets its own error.
+    fn fetch_batch(
+        &self,
+        config: &Config,
+        repos: &[ConfigRepo],
+        chunk: &[usize],
+        state: StateFilter,
+    ) -> Result<Vec<Result<Repo, GitHubError>>, GitHubError> {
+        let chunk_repos: Vec<&ConfigRepo> = chunk.iter().map(|&i| &repos[i]).collect();
+        let (query, variables) = batch_query(&chunk_repos, page_size(config, 0), state);
+        let (response, errors) = self.run_partial_query(
+            config,
+            chunk_repos[0],
+            with_fragments(&query, REPOSITORY_FRAGMENTS),
+            variables,
+            true,
+        )?;
+
+        Ok(batch_repos(response, errors, chunk_repos.len())?
+            .into_iter()
+            .zip(chunk_repos)
+            .map(|(repository, repo)| {
+                repository.and_then(|r| self.fetch_remaining(config, repo, state, r))
+            })
+            .collect())
+    }
+
+    /// Completes a repository whose first page of pull requests has been
+    /// fetched, following cursors up to `config.limit`.
+    fn fetch_remaining(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        state: StateFilter,
+        mut repository: Repo,
+    ) -> Result<Repo, GitHubError> {
+        loop {
+            let fetched = repository.pull_requests.nodes.len();
+            let done = !repository.pull_requests.has_next_page()
+                || config.limit.map_or(false, |limit| fetched >= limit);
+            if done {
+                break;
+            }
+
+            let response: RepoResponse = self.query(
+                config,
+                repo,
+                with_fragments(PULL_REQUESTS_QUERY, REPOSITORY_FRAGMENTS),
+                json!({
+                    "repo_owner": repo.owner,
+                    "repo_name": repo.name,
+                    "page_size": page_size(config, fetched),
+                    "cursor": repository.pull_requests.page_info.end_cursor,
+                    "states": state.graphql_states(),
+                }),
+            )?;
+            repository
+                .pull_requests
+                .extend(response.repository.pull_requests);
+        }
+
+        repository.rate_limit = self.rate_limit(config, repo);
         Ok(repository)
     }
+
+    fn fetch_pull_request_comments(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        pr: &mut PullRequest,
+    ) -> Result<(), GitHubError> {
+        while pr.comments.has_next_page() {
+            let page: NodeResponse<PullRequestComments> = self.query(
+                config,
+                repo,
+                with_fragments(PULL_REQUEST_COMMENTS_QUERY, &[COMMENT_FRAGMENT]),
+                json!({
+                    "id": pr.id,
+                    "cursor": pr.comments.page_info.end_cursor,
+                }),
+            )?;
+            pr.comments.extend(page.node.comments);
+        }
+
+        while pr.reviews.has_next_page() {
+            let page: NodeResponse<PullRequestReviews> = self.query(
+                config,
+                repo,
+                with_fragments(PULL_REQUEST_REVIEWS_QUERY, &[REVIEW_FRAGMENT]),
+                json!({
+                    "id": pr.id,
+                    "cursor": pr.reviews.page_info.end_cursor,
+                }),
+            )?;
+            pr.reviews.extend(page.node.reviews);
+        }
+
+        while pr.review_threads.has_next_page() {
+            let page: NodeResponse<PullRequestReviewThreads> = self.query(
+                config,
+                repo,
+                with_fragments(
+                    PULL_REQUEST_REVIEW_THREADS_QUERY,
+                    &[REVIEW_THREAD_FRAGMENT, REVIEW_COMMENT_FRAGMENT],
+                ),
+                json!({
+                    "id": pr.id,
+                    "cursor": pr.review_threads.page_info.end_cursor,
+                }),
+            )?;
+            pr.review_threads.extend(page.node.review_threads);
+        }
+
+        for thread in pr.review_threads.nodes.iter_mut() {
+            while thread.comments.has_next_page() {
+                let page: NodeResponse<ReviewComments> = self.query(
+                    config,
+                    repo,
+                    with_fragments(REVIEW_THREAD_COMMENTS_QUERY, &[REVIEW_COMMENT_FRAGMENT]),
+                    json!({
+                        "id": thread.id,
+                        "cursor": thread.comments.page_info.end_cursor,
+                    }),
+                )?;
+                thread.comments.extend(page.node.comments);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GithubAPI for GitHubGraphqlAPI {
+    fn list_pull_requests(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        state: StateFilter,
+    ) -> Result<Repo, GitHubError> {
+        let response: RepoResponse = self.query(
+            config,
+            repo,
+            with_fragments(PULL_REQUESTS_QUERY, REPOSITORY_FRAGMENTS),
+            json!({
+                "repo_owner": repo.owner,
+                "repo_name": repo.name,
+                "page_size": page_size(config, 0),
+                "cursor": null,
+                "states": state.graphql_states(),
+            }),
+        )?;
+        self.fetch_remaining(config, repo, state, response.repository)
+    }
+
+    fn fetch_repos<F>(
+        &self,
+        config: &Config,
+        repos: &[ConfigRepo],
+        state: StateFilter,
+        mut on_repo: F,
+    ) -> Result<(), GitHubError>
+    where
+        F: FnMut(&ConfigRepo, Result<Repo, GitHubError>),
+    {
+        let per_query = cmp::max(
+            1,
+            MAX_QUERY_NODES / cmp::max(1, page_size(config, 0) * NODES_PER_PULL_REQUEST),
+        );
+
+        // Repos can only share a query if they live on the same endpoint.
+        let mut endpoints: Vec<((String, &str), Vec<usize>)> = vec![];
+        for (index, repo) in repos.iter().enumerate() {
+            let endpoint = (config.api_url_for(repo), config.token_for(repo));
+            match endpoints.iter().position(|(e, _)| *e == endpoint) {
+                Some(position) => endpoints[position].1.push(index),
+                None => endpoints.push((endpoint, vec![index])),
+            }
+        }
+        let chunks: Vec<&[usize]> = endpoints
+            .iter()
+            .flat_map(|(_, indices)| indices.chunks(per_query))
+            .collect();
+
+        // Chunks of another endpoint can hold repos given earlier, so each is
+        // held back until every repo before it is in.
+        let mut fetched: Vec<Option<Result<Repo, GitHubError>>> =
+            repos.iter().map(|_| None).collect();
+        let mut next = 0;
+        pool::in_order(
+            &chunks,
+            concurrency(config),
+            |chunk| self.fetch_batch(config, repos, chunk, state),
+            |chunk, batch| {
+                for (&index, repository) in chunk.iter().zip(batch) {
+                    fetched[index] = Some(repository);
+                }
+                while let Some(repository) = fetched.get_mut(next).and_then(|r| r.take()) {
+                    on_repo(&repos[next], repository);
+                    next += 1;
+                }
+                Ok(true)
+            },
+        )
+    }
+
+    fn fetch_pull_request(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        number: i32,
+    ) -> Result<PullRequest, GitHubError> {
+        let response: PullRequestResponse = self.query(
+            config,
+            repo,
+            with_fragments(PULL_REQUEST_QUERY, PULL_REQUEST_FRAGMENTS),
+            json!({
+                "repo_owner": repo.owner,
+                "repo_name": repo.name,
+                "number": number,
+            }),
+        )?;
+        let mut pr = response.repository.pull_request;
+        self.fetch_pull_request_comments(config, repo, &mut pr)?;
+        Ok(pr)
+    }
+
+    fn fetch_rate_limit(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+    ) -> Result<RateLimit, GitHubError> {
+        let _: serde_json::Value = self.run_query(
+            config,
+            repo,
+            String::from(RATE_LIMIT_QUERY),
+            json!({}),
+            false,
+        )?;
+        self.rate_limit(config, repo).ok_or_else(|| {
+            GitHubError::MalformedResponse(String::from("Response did not include a rate limit."))
+        })
+    }
+
+    fn search_pull_requests(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        query: &str,
+    ) -> Result<Vec<PullRequest>, GitHubError> {
+        // Issues would come back as empty objects.
+        let query = format!("{} is:pr", query);
+        let mut results: Paginated<PullRequest> = Paginated::default();
+        loop {
+            let fetched = results.nodes.len();
+            let response: SearchResponse = self.query(
+                config,
+                repo,
+                with_fragments(SEARCH_QUERY, SEARCH_FRAGMENTS),
+                json!({
+                    "query": query,
+                    "page_size": page_size(config, fetched),
+                    "cursor": results.page_info.end_cursor,
+                }),
+            )?;
+            results.extend(response.search);
+
+            let done = !results.has_next_page()
+                || config
+                    .limit
+                    .map_or(false, |limit| results.nodes.len() >= limit);
+            if done {
+                break;
+            }
+        }
+        Ok(results.nodes)
+    }
+
+    fn fetch_parent(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+    ) -> Result<Option<ConfigRepo>, GitHubError> {
+        let response: RepositoryParentResponse = self.query(
+            config,
+            repo,
+            String::from(REPOSITORY_PARENT_QUERY),
+            json!({
+                "repo_owner": repo.owner,
+                "repo_name": repo.name,
+            }),
+        )?;
+        Ok(parent_repo(repo, response.repository))
+    }
+
+    fn list_branch_pull_requests(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        branch: &str,
+    ) -> Result<Vec<PullRequest>, GitHubError> {
+        let response: RepoResponse = self.query(
+            config,
+            repo,
+            with_fragments(BRANCH_PULL_REQUESTS_QUERY, SEARCH_FRAGMENTS),
+            json!({
+                "repo_owner": repo.owner,
+                "repo_name": repo.name,
+                "branch": branch,
+                "page_size": BRANCH_PULL_REQUESTS_PAGE_SIZE,
+            }),
+        )?;
+        Ok(response.repository.pull_requests.nodes)
+    }
+
+    fn set_draft(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        pull_request_id: &str,
+        draft: bool,
+    ) -> Result<bool, GitHubError> {
+        let (mutation, field) = if draft {
+            (CONVERT_TO_DRAFT_MUTATION, "convertPullRequestToDraft")
+        } else {
+            (
+                MARK_READY_FOR_REVIEW_MUTATION,
+                "markPullRequestReadyForReview",
+            )
+        };
+        let response: serde_json::Value = self.run_query(
+            config,
+            repo,
+            String::from(mutation),
+            json!({ "id": pull_request_id }),
+            false,
+        )?;
+        response
+            .pointer(&format!("/{}/pullRequest/isDraft", field))
+            .and_then(|d| d.as_bool())
+            .ok_or_else(|| {
+                GitHubError::MalformedResponse(String::from("Response is missing isDraft."))
+            })
+    }
+
+    fn merge_pull_request(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        pull_request_id: &str,
+        method: MergeMethod,
+        expected_head_oid: Option<&str>,
+    ) -> Result<(), GitHubError> {
+        let _: serde_json::Value = self.run_query(
+            config,
+            repo,
+            String::from(MERGE_MUTATION),
+            json!({
+                "id": pull_request_id,
+                "method": method.graphql_method(),
+                "head": expected_head_oid,
+            }),
+            false,
+        )?;
+        Ok(())
+    }
+
+    fn delete_ref(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        ref_id: &str,
+    ) -> Result<(), GitHubError> {
+        let _: serde_json::Value = self.run_query(
+            config,
+            repo,
+            String::from(DELETE_REF_MUTATION),
+            json!({ "id": ref_id }),
+            false,
+        )?;
+        Ok(())
+    }
+
+    fn add_review(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        pull_request_id: &str,
+        event: ReviewEvent,
+        body: &str,
+    ) -> Result<(), GitHubError> {
+        let _: serde_json::Value = self.run_query(
+            config,
+            repo,
+            String::from(ADD_REVIEW_MUTATION),
+            json!({
+                "id": pull_request_id,
+                "event": event.graphql_event(),
+                "body": body,
+            }),
+            false,
+        )?;
+        Ok(())
+    }
+
+    fn fetch_changed_files(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        number: i32,
+    ) -> Result<Vec<ChangedFile>, GitHubError> {
+        let mut files = Vec::new();
+        for page in 1..=MAX_CHANGED_FILES_PAGES {
+            let batch: Vec<ChangedFile> = self.rest_get(
+                config,
+                repo,
+                &format!(
+                    "/repos/{}/{}/pulls/{}/files?per_page={}&page={}",
+                    repo.owner, repo.name, number, CHANGED_FILES_PAGE_SIZE, page
+                ),
+            )?;
+            let last = batch.len() < CHANGED_FILES_PAGE_SIZE;
+            files.extend(batch);
+            if last {
+                break;
+            }
+        }
+        Ok(files)
+    }
+
+    fn rate_limit(&self, config: &Config, repo: &ConfigRepo) -> Option<RateLimit> {
+        self.rate_limits
+            .lock()
+            .unwrap()
+            .get(&config.api_url_for(repo))
+            .cloned()
+    }
+
+    fn data_as_of(&self) -> Option<DateTime<Utc>> {
+        self.cache.data_as_of()
+    }
 }
 
 impl GithubAPI for GitHubMockAPI {
-    fn fetch_repo(&self, _config: &Config, _repo: &ConfigRepo) -> Result<Repo, GitHubError> {
+    fn list_pull_requests(
+        &self,
+        _config: &Config,
+        _repo: &ConfigRepo,
+        state: StateFilter,
+    ) -> Result<Repo, GitHubError> {
         let s = r###"
         {
   "data": {
     "repository": {
-      "name": "atst",
       "pullRequests": {
         "edges": [
           {
@@ -165,8 +1817,19 @@ impl GithubAPI for GitHubMockAPI {
               "title": "[WIP] CircleCI CD",
               "body": "This PR adds deployment capabilities to the CircleCI configuration, and disables image pushing and deployment from Travis.",
               "number": 276,
+              "headRefName": "circleci-cd",
+              "isCrossRepository": false,
+              "headRepository": {
+                "name": "atst",
+                "owner": {
+                  "login": "dod-ccpo"
+                }
+              },
+              "headRefOid": "3b0f6c1e0a8f9a7b2c4d5e6f708192a3b4c5d6e7",
+              "headRef": {
+                "id": "MDM0OlJlZmNpcmNsZWNpLWNk"
+              },
               "url": "https://github.com/dod-ccpo/atst/pull/276",
-              "createdAt": "2018-09-12T15:49:42Z",
               "updatedAt": "2018-09-24T15:25:36Z",
               "closedAt": null,
               "mergedAt": null,
@@ -188,6 +1851,41 @@ impl GithubAPI for GitHubMockAPI {
               },
               "reviews": {
                 "edges": []
+              },
+              "commits": {
+                "edges": [
+                  {
+                    "node": {
+                      "commit": {
+                        "statusCheckRollup": {
+                          "state": "FAILURE",
+                          "contexts": {
+                            "edges": [
+                              {
+                                "node": {
+                                  "__typename": "CheckRun",
+                                  "name": "build",
+                                  "status": "COMPLETED",
+                                  "conclusion": "FAILURE",
+                                  "detailsUrl": "https://github.com/dod-ccpo/atst/runs/1"
+                                }
+                              },
+                              {
+                                "node": {
+                                  "__typename": "StatusContext",
+                                  "context": "ci/circleci",
+                                  "state": "PENDING",
+                                  "description": "Your tests are queued",
+                                  "targetUrl": "https://circleci.com/gh/dod-ccpo/atst/1"
+                                }
+                              }
+                            ]
+                          }
+                        }
+                      }
+                    }
+                  }
+                ]
               }
             }
           },
@@ -198,8 +1896,12 @@ impl GithubAPI for GitHubMockAPI {
               "title": "Help Content",
               "body": "Begin adding content for the help document:\r\nhttps://docs.google.com/document/d/1Y6RbHd0YMwDpxxowP07MvJvkfvxhsAgmKiYNjoVm9bo/edit#/\r\n\r\nContent is not final. Some work still needs to get done to handle overflowing tables.\r\n\r\n![screencapture-localhost-8000-help-2018-09-25-16_29_33](https://user-images.githubusercontent.com/38014252/46041275-5d942f80-c0e0-11e8-84eb-aa078ea26007.png)\r\n",
               "number": 340,
+              "headRefName": "help-content",
+              "headRefOid": "9d1c2b3a4f5e6d7c8b9a0f1e2d3c4b5a69788766",
+              "headRef": {
+                "id": "MDM0OlJlZmhlbHAtY29udGVudA=="
+              },
               "url": "https://github.com/dod-ccpo/atst/pull/340",
-              "createdAt": "2018-09-25T20:41:46Z",
               "updatedAt": "2018-10-01T12:48:55Z",
               "closedAt": null,
               "mergedAt": null,
@@ -235,73 +1937,32 @@ impl GithubAPI for GitHubMockAPI {
                 "edges": [
                   {
                     "node": {
-                      "id": "MDE3OlB1bGxSZXF1ZXN0UmV2aWV3MTU5MDQ4Mjc2",
                       "author": {
                         "login": "patricksmithdds"
                       },
                       "createdAt": "2018-09-26T15:06:31Z",
-                      "updatedAt": "2018-09-26T15:11:45Z",
                       "bodyText": "",
-                      "state": "APPROVED",
-                      "comments": {
-                        "edges": [
-                          {
-                            "node": {
-                              "author": {
-                                "login": "patricksmithdds"
-                              },
-                              "id": "MDI0OlB1bGxSZXF1ZXN0UmV2aWV3Q29tbWVudDIyMDYwMzYxMw==",
-                              "bodyText": "Should this link be to #how-are-the-jedi-idiq-clins-structured?",
-                              "diffHunk": "@@ -40,15 +40,148 @@\n       <div class='panel'>\n         <div class='panel__heading panel__heading--divider'>\n           <h1>\n-            <div class='h4'>JEDI Cloud Help Documentation</div>\n-            <span class='h1'>Help Topic</span>\n+            <div class='h4'>\n+              JEDI Cloud Help Documentation\n+            </div>\n+            <span class='h1'>\n+              Getting Started\n+            </span>\n           </h1>\n+\n+          <ul>\n+            <li><a href=\"#how-to-prepare-for-financial-verification-step\">How to prepare for Financial Verification step?</a></li>\n+            <li><a href=\"#how-are-the-jedi-idiq-clins-structured\">How are the JEDI ID/IQ CLINs structured?</a></li>\n+            <li><a href=\"#how-are-projects-organized-in-the-jedi-cloud\">How are projects organized in the JEDI Cloud?</a></li>\n+          </ul>\n         </div>\n \n         <div class='panel__content'>\n-          <p>So you see, since we're a small operation, we don't fall into the...uh...jurisdiction of the Empire. So you're part of the mining guild then? No, not actually. Our operation is small enough not to be noticed...which is advantageous for everybody since our customers are anxious to avoid attracting attention to themselves. Aren't you afraid the Empire's going to find out about this little operation and shut you down? That's always been a danger looming like a shadow over everything we've built here. But things have developed that will insure security. I've just made a deal that will keep the Empire out of here forever. We would be honored if you would join us. I had no choice. They arrived right before you did. I'm sorry. I'm sorry, too.</p>\n-          <p>Now will you move along, little fella? We're got a lot of work to do. No! No, no! Stay and help you, I will. Find your friend, hmm? I'm not looking for a friend, I'm looking for a Jedi Master. Oohhh. Jedi Master. Yoda. You seek Yoda. You know him? Mmm. Take you to him, I will. Yes, yes. But now, we must eat. Come. Good food. Come. Come, come. Stay here and watch after the camp, Artoo.</p>\n-          <p>Well done. Hold them in the security tower - and keep it quiet. Move. What do you think you're doing? We're getting out of here. I knew all along it had to be a mistake. Do you think that after what you did to Han we're going to trust you? I had no choice... What are you doing? Trust him, trust him! Oh, so we understand, don't we, Chewie? He had no choice. I'm just trying to help... We don't need any of your help. H-a-a-a... What? It sounds like Han. There's still a chance to save Han...I mean, at the East Platform... Chewie. I'm terribly sorry about all this. After all, he's only a Wookiee.</p>\n+          <h2 id='financial-verification'>Financial Verification</h2>\n+\n+          <h3 id='how-to-prepare-for-financial-verification-step'>How to prepare for Financial Verification step?</h3>\n+          <p>Once your request is approved, the next step is to create a Task Order (T.O.) associated with the JEDI Cloud ID/IQ.  Please contact a Contracting Officer (KO) or Contracting Officer Representative (COR) to help with this step. </p>\n+          <p>This may also involve talking to your Financial Manager (FM) to secure funding.</p>\n+          <p>Once the Task Order (T.O.) has been created, you will need to provide information related to the task order and funding in AT-AT. This step is referred to as “Financial Verification.”</p>\n+          <p><em>We also recommend getting familiar with the <a href=\"#\">JEDI Cloud CLIN structures</a> so that you know which specific services are available under JEDI and categorized for contracting purposes. This will help you and the Contracting Officer create a Task Order.</em></p>",
-                              "createdAt": "2018-09-26T15:06:31Z",
-                              "updatedAt": "2018-09-27T18:29:09Z"
-                            }
-                          }
-                        ]
-                      }
+                      "state": "APPROVED"
                     }
                   },
                   {
                     "node": {
-                      "id": "MDE3OlB1bGxSZXF1ZXN0UmV2aWV3MTU5MDc1MzEy",
                       "author": {
                         "login": "luisgov"
                       },
                       "createdAt": "2018-09-26T16:00:12Z",
-                      "updatedAt": "2018-09-26T16:00:12Z",
                       "bodyText": "",
-                      "state": "COMMENTED",
-                      "comments": {
-                        "edges": [
-                          {
-                            "node": {
-                              "author": {
-                                "login": "luisgov"
-                              },
-                              "id": "MDI0OlB1bGxSZXF1ZXN0UmV2aWV3Q29tbWVudDIyMDYyNTIxNw==",
-                              "bodyText": "👍",
-                              "diffHunk": "@@ -40,15 +40,148 @@\n       <div class='panel'>\n         <div class='panel__heading panel__heading--divider'>\n           <h1>\n-            <div class='h4'>JEDI Cloud Help Documentation</div>\n-            <span class='h1'>Help Topic</span>\n+            <div class='h4'>\n+              JEDI Cloud Help Documentation\n+            </div>\n+            <span class='h1'>\n+              Getting Started\n+            </span>\n           </h1>\n+\n+          <ul>\n+            <li><a href=\"#how-to-prepare-for-financial-verification-step\">How to prepare for Financial Verification step?</a></li>\n+            <li><a href=\"#how-are-the-jedi-idiq-clins-structured\">How are the JEDI ID/IQ CLINs structured?</a></li>\n+            <li><a href=\"#how-are-projects-organized-in-the-jedi-cloud\">How are projects organized in the JEDI Cloud?</a></li>\n+          </ul>\n         </div>\n \n         <div class='panel__content'>\n-          <p>So you see, since we're a small operation, we don't fall into the...uh...jurisdiction of the Empire. So you're part of the mining guild then? No, not actually. Our operation is small enough not to be noticed...which is advantageous for everybody since our customers are anxious to avoid attracting attention to themselves. Aren't you afraid the Empire's going to find out about this little operation and shut you down? That's always been a danger looming like a shadow over everything we've built here. But things have developed that will insure security. I've just made a deal that will keep the Empire out of here forever. We would be honored if you would join us. I had no choice. They arrived right before you did. I'm sorry. I'm sorry, too.</p>\n-          <p>Now will you move along, little fella? We're got a lot of work to do. No! No, no! Stay and help you, I will. Find your friend, hmm? I'm not looking for a friend, I'm looking for a Jedi Master. Oohhh. Jedi Master. Yoda. You seek Yoda. You know him? Mmm. Take you to him, I will. Yes, yes. But now, we must eat. Come. Good food. Come. Come, come. Stay here and watch after the camp, Artoo.</p>\n-          <p>Well done. Hold them in the security tower - and keep it quiet. Move. What do you think you're doing? We're getting out of here. I knew all along it had to be a mistake. Do you think that after what you did to Han we're going to trust you? I had no choice... What are you doing? Trust him, trust him! Oh, so we understand, don't we, Chewie? He had no choice. I'm just trying to help... We don't need any of your help. H-a-a-a... What? It sounds like Han. There's still a chance to save Han...I mean, at the East Platform... Chewie. I'm terribly sorry about all this. After all, he's only a Wookiee.</p>\n+          <h2 id='financial-verification'>Financial Verification</h2>\n+\n+          <h3 id='how-to-prepare-for-financial-verification-step'>How to prepare for Financial Verification step?</h3>\n+          <p>Once your request is approved, the next step is to create a Task Order (T.O.) associated with the JEDI Cloud ID/IQ.  Please contact a Contracting Officer (KO) or Contracting Officer Representative (COR) to help with this step. </p>\n+          <p>This may also involve talking to your Financial Manager (FM) to secure funding.</p>\n+          <p>Once the Task Order (T.O.) has been created, you will need to provide information related to the task order and funding in AT-AT. This step is referred to as “Financial Verification.”</p>\n+          <p><em>We also recommend getting familiar with the <a href=\"#\">JEDI Cloud CLIN structures</a> so that you know which specific services are available under JEDI and categorized for contracting purposes. This will help you and the Contracting Officer create a Task Order.</em></p>",
-                              "createdAt": "2018-09-26T16:00:12Z",
-                              "updatedAt": "2018-09-27T18:29:09Z"
-                            }
-                          }
-                        ]
-                      }
+                      "state": "COMMENTED"
                     }
                   },
                   {
                     "node": {
-                      "id": "MDE3OlB1bGxSZXF1ZXN0UmV2aWV3MTYwMjc4ODM3",
                       "author": {
                         "login": "andrewdds"
                       },
                       "createdAt": "2018-10-01T12:48:55Z",
-                      "updatedAt": "2018-10-01T12:48:55Z",
                       "bodyText": "Could you put manual widths on the table columns?",
-                      "state": "CHANGES_REQUESTED",
-                      "comments": {
-                        "edges": []
-                      }
+                      "state": "CHANGES_REQUESTED"
                     }
                   }
                 ]
@@ -315,8 +1976,40 @@ impl GithubAPI for GitHubMockAPI {
               "title": "Coverage #160694116",
               "body": "This adds `pytest-cov` per PT story https://www.pivotaltracker.com/n/projects/2160940/stories/160694116. Coverage runs by default in pytest and a total coverage score below 90 fails.\r\n\r\nWe're currently at 89%; I'm waiting on https://github.com/dod-ccpo/atst/pull/343 to be merged, since that should put us over the bar (thanks @montana-mil ).\r\n\r\n**note**\r\nThis locks our Python version at 3.6.6, instead of 3.6.*. `pytest-cov` breaks on lesser versions. This means if you're running some other minor version you will have to switch. We should prepare everyone and merge at a scheduled time we all know about.",
               "number": 350,
+              "headRefName": "coverage",
+              "headRefOid": "5e6f708192a3b4c5d6e7f8091a2b3c4d5e6f7081",
+              "headRef": {
+                "id": "MDM0OlJlZmNvdmVyYWdl"
+              },
+              "reviewRequests": {
+                "edges": [
+                  {
+                    "node": {
+                      "requestedReviewer": {
+                        "__typename": "User",
+                        "login": "luisgov"
+                      }
+                    }
+                  },
+                  {
+                    "node": {
+                      "requestedReviewer": {
+                        "__typename": "Team",
+                        "slug": "frontend",
+                        "combinedSlug": "dod-ccpo/frontend"
+                      }
+                    }
+                  },
+                  {
+                    "node": {
+                      "requestedReviewer": {
+                        "__typename": "Mannequin"
+                      }
+                    }
+                  }
+                ]
+              },
               "url": "https://github.com/dod-ccpo/atst/pull/350",
-              "createdAt": "2018-09-27T19:49:19Z",
               "updatedAt": "2018-10-02T13:36:58Z",
               "closedAt": null,
               "mergedAt": null,
@@ -371,8 +2064,19 @@ impl GithubAPI for GitHubMockAPI {
               "title": "End to end test #160690740",
               "body": "PT story: https://www.pivotaltracker.com/story/show/160690740\r\n\r\nThis adds a harness for end-to-end testing with Selenium and BrowserStack. This PR only adds two very basic acceptance tests as examples. I'd like to get eyes on the setup before we dive into adding a lot of new end-to-end tests.\r\n\r\n**note**\r\nI'm having the top-level script use a different database, `atat_selenium`. We could write a separate seed script to pre-populate this with data useful for the end-to-end tests without affecting the other databases. It's also possible the test factories will just work in this context, but I haven't gotten that far with it.",
               "number": 358,
+              "headRefName": "end-to-end-tests",
+              "isCrossRepository": true,
+              "headRepository": {
+                "name": "atst",
+                "owner": {
+                  "login": "dandds"
+                }
+              },
+              "headRefOid": "c4d5e6f708192a3b4c5d6e7f8091a2b3c4d5e6f7",
+              "headRef": {
+                "id": "MDM0OlJlZmVuZC10by1lbmQtdGVzdHM="
+              },
               "url": "https://github.com/dod-ccpo/atst/pull/358",
-              "createdAt": "2018-10-01T17:24:46Z",
               "updatedAt": "2018-10-02T17:58:25Z",
               "closedAt": null,
               "mergedAt": null,
@@ -438,8 +2142,68 @@ impl GithubAPI for GitHubMockAPI {
               "title": "Add Default String Representations for Models",
               "body": "## Description\r\nModels now have a `__repr__` method that prints some relevant information that should be helpful for debugging purposes.\r\nIt also adds another `auth` test.\r\nCollaboration with @dandds \r\n\r\n## Pivotal Tracker\r\nhttps://www.pivotaltracker.com/story/show/160694209",
               "number": 370,
+              "headRefName": "model-repr",
+              "headRefOid": "0f1e2d3c4b5a69788766554433221100ffeeddcc",
+              "headRef": {
+                "id": "MDM0OlJlZm1vZGVsLXJlcHI="
+              },
+              "reviewThreads": {
+                "edges": [
+                  {
+                    "node": {
+                      "id": "MDIzOlB1bGxSZXF1ZXN0UmV2aWV3VGhyZWFkMQ==",
+                      "path": "tests/models/test_environments.py",
+                      "line": 22,
+                      "isResolved": false,
+                      "isOutdated": false,
+                      "comments": {
+                        "edges": [
+                          {
+                            "node": {
+                              "author": {
+                                "login": "patricksmithdds"
+                              },
+                              "id": "MDI0OlB1bGxSZXF1ZXN0UmV2aWV3Q29tbWVudDIyMjA5ODYyOA==",
+                              "bodyText": "We can axe this test.",
+                              "diffHunk": "@@ -16,3 +16,7 @@ def test_add_user_to_environment():\n \n     dev_environment = Environments.add_member(dev_environment, developer, \"developer\")\n     assert developer in dev_environment.users\n+\n+\n+def test_repr():\n+    pass",
+                              "createdAt": "2018-10-02T20:23:31Z",
+                              "updatedAt": "2018-10-02T20:25:51Z"
+                            }
+                          }
+                        ]
+                      }
+                    }
+                  },
+                  {
+                    "node": {
+                      "id": "MDIzOlB1bGxSZXF1ZXN0UmV2aWV3VGhyZWFkMg==",
+                      "path": "atst/models/user.py",
+                      "line": null,
+                      "isResolved": true,
+                      "isOutdated": true,
+                      "comments": {
+                        "edges": [
+                          {
+                            "node": {
+                              "author": {
+                                "login": "dandds"
+                              },
+                              "id": "MDI0OlB1bGxSZXF1ZXN0UmV2aWV3Q29tbWVudDIyMjA5OTAwMQ==",
+                              "bodyText": "Should this include the email?",
+                              "diffHunk": "@@ -1,3 +1,6 @@\n+    def __repr__(self):\n+        return \"<User(name='{}', dod_id='{}', id='{}')>\".format(",
+                              "createdAt": "2018-10-02T19:03:11Z",
+                              "updatedAt": "2018-10-02T19:03:11Z"
+                            }
+                          }
+                        ]
+                      }
+                    }
+                  }
+                ]
+              },
+              "reviewDecision": "REVIEW_REQUIRED",
+              "isDraft": true,
               "url": "https://github.com/dod-ccpo/atst/pull/370",
-              "createdAt": "2018-10-02T18:16:32Z",
               "updatedAt": "2018-10-02T20:25:51Z",
               "closedAt": null,
               "mergedAt": null,
@@ -457,30 +2221,12 @@ impl GithubAPI for GitHubMockAPI {
                 "edges": [
                   {
                     "node": {
-                      "id": "MDE3OlB1bGxSZXF1ZXN0UmV2aWV3MTYwOTA2ODg3",
                       "author": {
                         "login": "patricksmithdds"
                       },
                       "createdAt": "2018-10-02T20:23:31Z",
-                      "updatedAt": "2018-10-02T20:25:51Z",
                       "bodyText": "",
-                      "state": "COMMENTED",
-                      "comments": {
-                        "edges": [
-                          {
-                            "node": {
-                              "author": {
-                                "login": "patricksmithdds"
-                              },
-                              "id": "MDI0OlB1bGxSZXF1ZXN0UmV2aWV3Q29tbWVudDIyMjA5ODYyOA==",
-                              "bodyText": "We can axe this test.",
-                              "diffHunk": "@@ -16,3 +16,7 @@ def test_add_user_to_environment():\n \n     dev_environment = Environments.add_member(dev_environment, developer, \"developer\")\n     assert developer in dev_environment.users\n+\n+\n+def test_repr():\n+    pass",
-                              "createdAt": "2018-10-02T20:23:31Z",
-                              "updatedAt": "2018-10-02T20:25:51Z"
-                            }
-                          }
-                        ]
-                      }
+                      "state": "COMMENTED"
                     }
                   }
                 ]
@@ -493,27 +2239,725 @@ impl GithubAPI for GitHubMockAPI {
   }
 }
         "###;
-        let repository = parse_repo_response(s.to_string())?;
+        let mut repository = parse_repo_response(s.to_string())?;
+        repository
+            .pull_requests
+            .nodes
+            .retain(|pr| state.graphql_states().contains(&pr.state.as_str()));
         Ok(repository)
     }
+
+    fn fetch_pull_request(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        number: i32,
+    ) -> Result<PullRequest, GitHubError> {
+        self.list_pull_requests(config, repo, StateFilter::All)?
+            .pull_requests
+            .nodes
+            .into_iter()
+            .find(|pr| pr.number == number)
+            .ok_or_else(|| GitHubError::NotFound(format!("No pull request #{}.", number)))
+    }
+
+    fn fetch_rate_limit(
+        &self,
+        _config: &Config,
+        _repo: &ConfigRepo,
+    ) -> Result<RateLimit, GitHubError> {
+        let s = r###"
+        {
+  "data": {
+    "rateLimit": {
+      "limit": 5000,
+      "cost": 1,
+      "remaining": 4231,
+      "resetAt": "2018-10-02T21:00:00Z"
+    }
+  }
+}
+        "###;
+        let response: RateLimitedResponse<serde_json::Value> = parse_response(s.to_string())?;
+        response.rate_limit.ok_or_else(|| {
+            GitHubError::MalformedResponse(String::from("Response did not include a rate limit."))
+        })
+    }
+    fn search_pull_requests(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        _query: &str,
+    ) -> Result<Vec<PullRequest>, GitHubError> {
+        let mut prs = self
+            .list_pull_requests(config, repo, StateFilter::All)?
+            .pull_requests
+            .nodes;
+        for pr in prs.iter_mut() {
+            pr.repository = Some(PullRequestRepository {
+                name: String::from("atst"),
+                owner: User {
+                    login: String::from("dod-ccpo"),
+                },
+            });
+        }
+        Ok(prs)
+    }
+
+    fn fetch_parent(
+        &self,
+        _config: &Config,
+        repo: &ConfigRepo,
+    ) -> Result<Option<ConfigRepo>, GitHubError> {
+        // dandds/atst is a fork of dod-ccpo/atst.
+        let s = if repo.owner == "dandds" {
+            r###"
+        {
+  "data": {
+    "repository": {
+      "isFork": true,
+      "parent": {
+        "name": "atst",
+        "owner": {
+          "login": "dod-ccpo"
+        }
+      }
+    }
+  }
+}
+        "###
+        } else {
+            r###"
+        {
+  "data": {
+    "repository": {
+      "isFork": false,
+      "parent": null
+    }
+  }
+}
+        "###
+        };
+        let response: RepositoryParentResponse = parse_response(s.to_string())?;
+        Ok(parent_repo(repo, response.repository))
+    }
+
+    fn list_branch_pull_requests(
+        &self,
+        config: &Config,
+        repo: &ConfigRepo,
+        branch: &str,
+    ) -> Result<Vec<PullRequest>, GitHubError> {
+        let mut prs = self
+            .list_pull_requests(config, repo, StateFilter::All)?
+            .pull_requests
+            .nodes;
+        prs.retain(|pr| {
+            pr.head_ref_name
+                .as_ref()
+                .map_or(false, |name| name == branch)
+        });
+        prs.reverse();
+        Ok(prs)
+    }
+
+    fn set_draft(
+        &self,
+        _config: &Config,
+        _repo: &ConfigRepo,
+        pull_request_id: &str,
+        draft: bool,
+    ) -> Result<bool, GitHubError> {
+        self.mutations.lock().unwrap().push(Mutation::SetDraft {
+            pull_request_id: String::from(pull_request_id),
+            draft,
+        });
+        Ok(draft)
+    }
+
+    fn merge_pull_request(
+        &self,
+        _config: &Config,
+        _repo: &ConfigRepo,
+        pull_request_id: &str,
+        method: MergeMethod,
+        expected_head_oid: Option<&str>,
+    ) -> Result<(), GitHubError> {
+        self.mutations.lock().unwrap().push(Mutation::Merge {
+            pull_request_id: String::from(pull_request_id),
+            method,
+            expected_head_oid: expected_head_oid.map(String::from),
+        });
+        Ok(())
+    }
+
+    fn delete_ref(
+        &self,
+        _config: &Config,
+        _repo: &ConfigRepo,
+        ref_id: &str,
+    ) -> Result<(), GitHubError> {
+        self.mutations.lock().unwrap().push(Mutation::DeleteRef {
+            ref_id: String::from(ref_id),
+        });
+        Ok(())
+    }
+
+    fn add_review(
+        &self,
+        _config: &Config,
+        _repo: &ConfigRepo,
+        pull_request_id: &str,
+        event: ReviewEvent,
+        body: &str,
+    ) -> Result<(), GitHubError> {
+        self.mutations.lock().unwrap().push(Mutation::AddReview {
+            pull_request_id: String::from(pull_request_id),
+            event,
+            body: String::from(body),
+        });
+        Ok(())
+    }
+
+    fn fetch_changed_files(
+        &self,
+        _config: &Config,
+        _repo: &ConfigRepo,
+        _number: i32,
+    ) -> Result<Vec<ChangedFile>, GitHubError> {
+        let s = r###"
+[
+  {
+    "sha": "5f1c0ba3e9d0c2b1a7e6f4d3c2b1a0f9e8d7c6b5",
+    "filename": "atst/models/user.py",
+    "status": "modified",
+    "additions": 4,
+    "deletions": 1,
+    "changes": 5,
+    "patch": "@@ -28,7 +28,10 @@ class User(Base):\n     def full_name(self):\n         return \"{} {}\".format(self.first_name, self.last_name)\n \n-    def __repr__(self):\n+    def __repr__(self):\n+        return \"<User(name='{}', dod_id='{}', email='{}', id='{}')>\".format(\n+            self.full_name, self.dod_id, self.email, self.id\n+        )\n"
+  },
+  {
+    "sha": "0a9b8c7d6e5f4a3b2c1d0e9f8a7b6c5d4e3f2a1b",
+    "filename": "tests/models/test_environments.py",
+    "status": "added",
+    "additions": 22,
+    "deletions": 0,
+    "changes": 22,
+    "patch": "@@ -0,0 +1,3 @@\n+from atst.models.environment import Environment\n+\n+def test_add_user_to_environment():\n"
+  },
+  {
+    "sha": "9e8d7c6b5a4f3e2d1c0b9a8f7e6d5c4b3a2f1e0d",
+    "filename": "static/img/logo.png",
+    "status": "modified",
+    "additions": 0,
+    "deletions": 0,
+    "changes": 0
+  }
+]
+        "###;
+        Ok(serde_json::from_str(s)?)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use types::{ApprovalState, CiStatus, MergeBlocker};
 
     #[test]
     fn parse() {
-        let mock_api = GitHubMockAPI {};
+        let mock_api = GitHubMockAPI::default();
+        let config = Config {
+            me: "Richard".to_string(),
+            token: "hello".to_string(),
+            ..Default::default()
+        };
+        let repo = ConfigRepo {
+            owner: String::from("me"),
+            name: String::from("repo"),
+            ..Default::default()
+        };
+        mock_api
+            .list_pull_requests(&config, &repo, StateFilter::Open)
+            .unwrap();
+    }
+
+    #[test]
+    fn parse_rate_limit() {
+        let mock_api = GitHubMockAPI::default();
         let config = Config {
             me: "Richard".to_string(),
             token: "hello".to_string(),
+            ..Default::default()
         };
         let repo = ConfigRepo {
             owner: String::from("me"),
             name: String::from("repo"),
+            ..Default::default()
+        };
+        let rate_limit = mock_api.fetch_rate_limit(&config, &repo).unwrap();
+        assert_eq!(rate_limit.remaining, 4231);
+        assert_eq!(rate_limit.limit, 5000);
+    }
+
+    #[test]
+    fn list_pull_requests_by_state() {
+        let mock_api = GitHubMockAPI::default();
+        let config = Config::default();
+        let repo = ConfigRepo::default();
+        let open = mock_api
+            .list_pull_requests(&config, &repo, StateFilter::Open)
+            .unwrap();
+        assert_eq!(open.pull_requests.nodes.len(), 5);
+        let merged = mock_api
+            .list_pull_requests(&config, &repo, StateFilter::Merged)
+            .unwrap();
+        assert!(merged.pull_requests.nodes.is_empty());
+        assert_eq!("all".parse::<StateFilter>().unwrap(), StateFilter::All);
+        assert!("draft".parse::<StateFilter>().is_err());
+    }
+
+    #[test]
+    fn parse_status_checks() {
+        let mock_api = GitHubMockAPI::default();
+        let config = Config::default();
+        let repo = ConfigRepo::default();
+        let pr = mock_api.fetch_pull_request(&config, &repo, 276).unwrap();
+        assert_eq!(pr.ci_status(), Some(CiStatus::Failing));
+        let checks = &pr.status_check_rollup().unwrap().contexts.nodes;
+        assert_eq!(checks[0].name(), "build");
+        assert_eq!(checks[0].status(), CiStatus::Failing);
+        assert_eq!(checks[1].name(), "ci/circleci");
+        assert_eq!(checks[1].status(), CiStatus::Pending);
+        assert_eq!(
+            checks[1].url(),
+            Some("https://circleci.com/gh/dod-ccpo/atst/1")
+        );
+
+        let pr = mock_api.fetch_pull_request(&config, &repo, 340).unwrap();
+        assert_eq!(pr.ci_status(), None);
+    }
+
+    #[test]
+    fn parse_review_requests() {
+        let mock_api = GitHubMockAPI::default();
+        let config = Config::default();
+        let repo = ConfigRepo::default();
+        let pr = mock_api.fetch_pull_request(&config, &repo, 350).unwrap();
+        assert!(pr.is_review_requested_from(&["LuisGov"]));
+        assert!(pr.is_review_requested_from(&["frontend"]));
+        assert!(pr.is_review_requested_from(&["dod-ccpo/frontend"]));
+        assert!(!pr.is_review_requested_from(&["andrewdds", "backend"]));
+    }
+
+    #[test]
+    fn work_in_progress() {
+        let mock_api = GitHubMockAPI::default();
+        let repo = ConfigRepo::default();
+        let wip_numbers = |config: &Config| -> Vec<i32> {
+            mock_api
+                .list_pull_requests(config, &repo, StateFilter::Open)
+                .unwrap()
+                .pull_requests
+                .nodes
+                .iter()
+                .filter(|pr| config.is_work_in_progress(pr))
+                .map(|pr| pr.number)
+                .collect()
+        };
+        assert_eq!(wip_numbers(&Config::default()).len(), 5);
+        let config = Config {
+            wip_title_prefixes: Some(vec![String::from("[WIP]")]),
+            wip_labels: Some(vec![]),
+            ..Default::default()
+        };
+        assert_eq!(wip_numbers(&config), vec![276, 370]);
+    }
+
+    #[test]
+    fn approval_state() {
+        let mock_api = GitHubMockAPI::default();
+        let config = Config::default();
+        let repo = ConfigRepo::default();
+
+        // Approved, then changes were requested by someone else.
+        let pr = mock_api.fetch_pull_request(&config, &repo, 340).unwrap();
+        assert_eq!(pr.approval_state(), ApprovalState::ChangesRequested);
+        let latest: Vec<(&str, &str)> = pr
+            .latest_reviews()
+            .iter()
+            .map(|r| (r.author.login.as_str(), r.state.as_str()))
+            .collect();
+        assert_eq!(
+            latest,
+            vec![
+                ("patricksmithdds", "APPROVED"),
+                ("luisgov", "COMMENTED"),
+                ("andrewdds", "CHANGES_REQUESTED"),
+            ]
+        );
+        assert_eq!(
+            pr.latest_reviews()[2].body_text,
+            "Could you put manual widths on the table columns?"
+        );
+
+        let pr = mock_api.fetch_pull_request(&config, &repo, 370).unwrap();
+        assert_eq!(pr.approval_state(), ApprovalState::ReviewRequired);
+
+        let pr = mock_api.fetch_pull_request(&config, &repo, 276).unwrap();
+        assert_eq!(pr.approval_state(), ApprovalState::None);
+    }
+
+    #[test]
+    fn parse_review_threads() {
+        let mock_api = GitHubMockAPI::default();
+        let config = Config::default();
+        let repo = ConfigRepo::default();
+        let pr = mock_api.fetch_pull_request(&config, &repo, 370).unwrap();
+        let threads = &pr.review_threads.nodes;
+        assert_eq!(threads.len(), 2);
+        assert_eq!(threads[0].path, "tests/models/test_environments.py");
+        assert_eq!(threads[0].line, Some(22));
+        assert!(!threads[0].is_resolved);
+        assert!(threads[0]
+            .diff_hunk()
+            .unwrap()
+            .starts_with("@@ -16,3 +16,7 @@"));
+        assert!(threads[1].is_resolved && threads[1].is_outdated);
+        assert_eq!(threads[1].line, None);
+    }
+
+    #[test]
+    fn parse_search_response() {
+        let body = r###"
+        {
+  "data": {
+    "search": {
+      "pageInfo": {
+        "hasNextPage": false,
+        "endCursor": "Y3Vyc29yOjE="
+      },
+      "edges": [
+        {
+          "node": {
+            "repository": {
+              "name": "atst",
+              "owner": {
+                "login": "dod-ccpo"
+              }
+            },
+            "id": "MDExOlB1bGxSZXF1ZXN0MjIyMjc4NjAx",
+            "state": "OPEN",
+            "title": "Add Default String Representations for Models",
+            "number": 370,
+            "url": "https://github.com/dod-ccpo/atst/pull/370",
+            "updatedAt": "2018-10-02T20:25:51Z",
+            "closedAt": null,
+            "mergedAt": null,
+            "mergeable": "MERGEABLE",
+            "author": {
+              "login": "richard-dds"
+            },
+            "labels": {
+              "edges": []
+            },
+            "reviews": {
+              "edges": []
+            }
+          }
+        }
+      ]
+    }
+  }
+}
+        "###;
+        let response: SearchResponse = parse_response(body.to_string()).unwrap();
+        let pr = &response.search.nodes[0];
+        let repository = pr.repository.as_ref().unwrap();
+        assert_eq!(repository.owner.login, "dod-ccpo");
+        assert_eq!(repository.name, "atst");
+        assert_eq!(pr.number, 370);
+    }
+
+    #[test]
+    fn parse_changed_files() {
+        let mock_api = GitHubMockAPI::default();
+        let files = mock_api
+            .fetch_changed_files(&Config::default(), &ConfigRepo::default(), 370)
+            .unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0].additions, 4);
+        assert!(files[0].patch.as_ref().unwrap().starts_with("@@ -28,7"));
+        assert!(files[2].patch.is_none());
+
+        let under_tests: Vec<&str> = files
+            .iter()
+            .filter(|f| f.is_under("tests/"))
+            .map(|f| f.filename.as_str())
+            .collect();
+        assert_eq!(under_tests, vec!["tests/models/test_environments.py"]);
+        assert!(files[0].is_under("atst/models/user.py"));
+        assert!(!files[0].is_under("atst/model"));
+    }
+
+    #[test]
+    fn find_pull_request_for_branch() {
+        let mock_api = GitHubMockAPI::default();
+        let config = Config::default();
+        let repo = ConfigRepo::default();
+        let pr = find_branch_pull_request(&config, &repo, &mock_api, "help-content").unwrap();
+        assert_eq!(pr.map(|pr| pr.number), Some(340));
+        let pr = find_branch_pull_request(&config, &repo, &mock_api, "master").unwrap();
+        assert!(pr.is_none());
+    }
+
+    #[test]
+    fn fork_parent() {
+        let mock_api = GitHubMockAPI::default();
+        let config = Config::default();
+        let fork = ConfigRepo {
+            owner: String::from("dandds"),
+            name: String::from("atst"),
+            host: Some(String::from("github.com")),
+            api_url: None,
         };
-        let _repo = mock_api.fetch_repo(&config, &repo).unwrap();
-        assert!(true)
+        let parent = mock_api.fetch_parent(&config, &fork).unwrap().unwrap();
+        assert_eq!(
+            (parent.owner.as_str(), parent.name.as_str()),
+            ("dod-ccpo", "atst")
+        );
+        assert_eq!(parent.host, fork.host);
+        assert!(mock_api.fetch_parent(&config, &parent).unwrap().is_none());
+
+        let prs = mock_api
+            .list_pull_requests(&config, &parent, StateFilter::All)
+            .unwrap()
+            .pull_requests
+            .nodes;
+        let from_fork: Vec<i32> = prs
+            .iter()
+            .filter(|pr| pr.is_from(&fork))
+            .map(|pr| pr.number)
+            .collect();
+        assert_eq!(from_fork, vec![358]);
+    }
+
+    #[test]
+    fn mock_records_mutations() {
+        let mock_api = GitHubMockAPI::default();
+        let config = Config::default();
+        let repo = ConfigRepo::default();
+        let pr = mock_api.fetch_pull_request(&config, &repo, 340).unwrap();
+        mock_api
+            .add_review(&config, &repo, &pr.id, ReviewEvent::Approve, "")
+            .unwrap();
+        assert!(mock_api.set_draft(&config, &repo, &pr.id, true).unwrap());
+        assert_eq!(
+            *mock_api.mutations.lock().unwrap(),
+            vec![
+                Mutation::AddReview {
+                    pull_request_id: String::from("MDExOlB1bGxSZXF1ZXN0MjE4MTEyNTg3"),
+                    event: ReviewEvent::Approve,
+                    body: String::new(),
+                },
+                Mutation::SetDraft {
+                    pull_request_id: String::from("MDExOlB1bGxSZXF1ZXN0MjE4MTEyNTg3"),
+                    draft: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_blockers() {
+        let mock_api = GitHubMockAPI::default();
+        let config = Config::default();
+        let repo = ConfigRepo::default();
+
+        let pr = mock_api.fetch_pull_request(&config, &repo, 276).unwrap();
+        assert_eq!(
+            pr.merge_blockers(),
+            vec![
+                MergeBlocker::NotApproved,
+                MergeBlocker::ChecksFailing(vec![String::from("build")]),
+            ]
+        );
+        assert_eq!(
+            pr.merge_blockers()[1].to_string(),
+            "Checks are failing: build."
+        );
+
+        let pr = mock_api.fetch_pull_request(&config, &repo, 340).unwrap();
+        assert_eq!(pr.merge_blockers(), vec![MergeBlocker::ChangesRequested]);
+
+        let pr = mock_api.fetch_pull_request(&config, &repo, 370).unwrap();
+        assert_eq!(
+            pr.merge_blockers(),
+            vec![MergeBlocker::Draft, MergeBlocker::NotApproved]
+        );
+    }
+
+    #[test]
+    fn find_pull_request_in_first_repo_with_it() {
+        let config = Config {
+            concurrency: Some(3),
+            ..Default::default()
+        };
+        let repos: Vec<ConfigRepo> = (0..8)
+            .map(|i| ConfigRepo {
+                owner: String::from("me"),
+                name: format!("repo{}", i),
+                ..Default::default()
+            })
+            .collect();
+
+        let (repo, pr) = find_pull_request(&config, &repos, &GitHubMockAPI::default(), 340)
+            .unwrap()
+            .unwrap();
+        assert_eq!(repo.name, "repo0");
+        assert_eq!(pr.title, "Help Content");
+
+        let missing = find_pull_request(&config, &repos, &GitHubMockAPI::default(), 1).unwrap();
+        assert!(missing.is_none());
+    }
+
+    #[test]
+    fn build_batch_query() {
+        let repos: Vec<ConfigRepo> = vec!["atst", "pears"]
+            .into_iter()
+            .map(|name| ConfigRepo {
+                owner: String::from("me"),
+                name: String::from(name),
+                ..Default::default()
+            })
+            .collect();
+        let (query, variables) =
+            batch_query(&repos.iter().collect::<Vec<_>>(), 20, StateFilter::Merged);
+
+        assert!(query.contains(
+            "fetchRepositories($page_size: Int!, $cursor: String, $states: [PullRequestState!], \
+             $owner0: String!, $name0: String!, $owner1: String!, $name1: String!)"
+        ));
+        assert!(query.contains("r1: repository(owner: $owner1, name: $name1)"));
+        assert_eq!(variables["name1"], "pears");
+        assert_eq!(variables["page_size"], 20);
+        assert_eq!(variables["states"], json!(["MERGED"]));
+    }
+
+    #[test]
+    fn wait_at_least_a_little_for_rate_limits() {
+        let now = Utc.ymd(2018, 11, 1).and_hms(12, 0, 0);
+        assert_eq!(
+            reset_wait(now + ::chrono::Duration::minutes(5), now),
+            time::Duration::from_secs(300)
+        );
+        assert_eq!(reset_wait(now, now), MIN_RATE_LIMIT_WAIT);
+        assert_eq!(
+            reset_wait(now - ::chrono::Duration::minutes(5), now),
+            MIN_RATE_LIMIT_WAIT
+        );
+    }
+
+    #[test]
+    fn parse_graphql_errors() {
+        let response = r#"{
+            "data": {"repository": null},
+            "errors": [{
+                "type": "NOT_FOUND",
+                "path": ["repository"],
+                "message": "Could not resolve to a Repository with the name 'atst'."
+            }]
+        }"#;
+        match parse_repo_response(response.to_string()) {
+            Err(GitHubError::NotFound(message)) => assert!(message.contains("atst")),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn batch_errors_stay_with_their_repos() {
+        let response = r#"{
+            "data": {
+                "r0": {"pullRequests": {"edges": []}},
+                "r1": null
+            },
+            "errors": [{
+                "type": "NOT_FOUND",
+                "path": ["r1"],
+                "message": "Could not resolve to a Repository with the name 'gone'."
+            }]
+        }"#;
+        let (data, errors) = parse_partial_response(response.to_string()).unwrap();
+        let repos = batch_repos(data, errors, 2).unwrap();
+        assert!(repos[0].is_ok());
+        match repos[1] {
+            Err(GitHubError::NotFound(ref message)) => assert!(message.contains("gone")),
+            ref other => panic!("unexpected result: {:?}", other),
+        }
+
+        let response = r#"{
+            "data": {"r0": null},
+            "errors": [{"type": "FORBIDDEN", "message": "Nope."}]
+        }"#;
+        assert!(
+            parse_partial_response::<HashMap<String, Option<Repo>>>(response.to_string()).is_err()
+        );
+    }
+
+    #[test]
+    fn tolerate_unreadable_team_review_requests() {
+        let response = r#"{
+            "data": {"node": {"requestedReviewer": null}},
+            "errors": [{
+                "type": "INSUFFICIENT_SCOPES",
+                "path": ["node", "requestedReviewer"],
+                "message": "Your token has not been granted the required scopes to execute this query."
+            }]
+        }"#;
+        let data: serde_json::Value = parse_response(response.to_string()).unwrap();
+        assert!(data["node"]["requestedReviewer"].is_null());
+
+        let response = r#"{
+            "data": null,
+            "errors": [{
+                "type": "INSUFFICIENT_SCOPES",
+                "path": ["node", "requestedReviewer"],
+                "message": "Your token has not been granted the required scopes to execute this query."
+            }]
+        }"#;
+        match parse_response::<serde_json::Value>(response.to_string()) {
+            Err(GitHubError::InsufficientScopes(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_http_errors() {
+        let response = r#"{"message": "Bad credentials"}"#;
+        match http_error(401, false, response) {
+            GitHubError::Authentication(message) => assert_eq!(message, "Bad credentials"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        match http_error(502, false, "<html>Bad Gateway</html>") {
+            GitHubError::Network(message) => assert_eq!(message, "HTTP 502"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let response = r#"{"message": "Resource protected by organization SAML enforcement."}"#;
+        match http_error(403, false, response) {
+            GitHubError::Forbidden(message) => assert!(message.contains("SAML")),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match http_error(403, true, r#"{"message": "API rate limit exceeded."}"#) {
+            GitHubError::RateLimited(_) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("X-RateLimit-Remaining", "12".parse().unwrap());
+        assert!(!rate_limit_exhausted(&headers));
+        headers.insert("X-RateLimit-Remaining", "0".parse().unwrap());
+        assert!(rate_limit_exhausted(&headers));
     }
 }
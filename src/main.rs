@@ -12,71 +12,438 @@ extern crate serde_derive;
 #[macro_use]
 extern crate serde_json;
 
+mod cache;
 mod config;
 mod display;
+mod editor;
 mod git;
 mod github;
+mod pool;
 mod types;
 
-use clap::{App, Arg, SubCommand};
+use cache::{Cache, CacheMode};
+use clap::{App, Arg, ArgGroup, ArgMatches, SubCommand};
 use config::read_config_file;
 use display::PearsDisplay;
-use git::{discover_repo, parse_repo_description};
-use github::{GitHubGraphqlAPI, GithubAPI};
+use git::{
+    checkout_pull_request, discover_repo, head_branch, local_branch_states, merge_conflicts,
+    parse_pull_request_reference, parse_repo_description, remote_for,
+};
+use github::{find_branch_pull_request, find_pull_request, GitHubGraphqlAPI, GithubAPI};
+use std::cmp;
+use std::collections::HashMap;
 use std::env;
-use types::{Config, ConfigRepo, PearsError, PullRequest};
+use types::{
+    Config, ConfigRepo, HeadBranch, LocalBranchState, MergeMethod, PearsError, PullRequest,
+    RateLimit, ReviewEvent, StateFilter,
+};
 
+fn warn_rate_limit(config: &Config, rate_limit: Option<&RateLimit>, display: &PearsDisplay) {
+    if let Some(rate_limit) = rate_limit {
+        let threshold = config.rate_limit_warning.unwrap_or(rate_limit.limit / 10);
+        if rate_limit.remaining < threshold {
+            display.rate_limit_warning(rate_limit);
+        }
+    }
+}
+
+/// Narrows down the pull requests `list` shows.
+struct Filters<'a> {
+    only_me: bool,
+    /// Users and teams a review must be requested from.
+    review_requested: Option<Vec<&'a str>>,
+    hide_drafts: bool,
+    only_drafts: bool,
+    /// Only those with a local branch.
+    local_only: bool,
+}
+
+impl<'a> Filters<'a> {
+    fn matches(&self, config: &Config, pr: &PullRequest) -> bool {
+        if self.only_me && pr.author.login != config.me {
+            return false;
+        }
+        if let Some(ref reviewers) = self.review_requested {
+            if !pr.is_review_requested_from(reviewers) {
+                return false;
+            }
+        }
+        if self.hide_drafts || self.only_drafts {
+            return config.is_work_in_progress(pr) == self.only_drafts;
+        }
+        true
+    }
+}
+
+/// How local branches compare with `prs`, pull requests of `repo`, when run in
+/// a checkout. Without `--local-only`, pull requests without a local branch are
+/// kept.
+fn local_branches<'a>(
+    config: &Config,
+    filters: &Filters,
+    repo: &ConfigRepo,
+    mut prs: Vec<&'a PullRequest>,
+) -> (Vec<&'a PullRequest>, HashMap<i32, LocalBranchState>) {
+    // Outside a checkout there is just nothing to show.
+    let states = env::current_dir()
+        .ok()
+        .and_then(|cwd| {
+            local_branch_states(cwd, repo, &prs, |pr| config.checkout_branch_name(pr)).ok()
+        })
+        .unwrap_or_default();
+    if filters.local_only {
+        prs.retain(|pr| states.contains_key(&pr.number));
+    }
+    (prs, states)
+}
+
+/// Lists pull requests, labelling those from `fork` when listing its parent.
 fn list<T: GithubAPI>(
     config: &Config,
-    config_repos: &Vec<ConfigRepo>,
-    only_me: bool,
+    config_repos: &[ConfigRepo],
+    fork: Option<&ConfigRepo>,
+    filters: &Filters,
+    state: StateFilter,
     api: T,
     display: PearsDisplay,
 ) -> Result<(), PearsError> {
-    for config_repo in config_repos {
-        let repo = api
-            .fetch_repo(config, &config_repo)
-            .expect("Could not reach GitHub API.");
+    if let Some(fork) = fork {
+        display.fork_parent(fork, &config_repos[0]);
+    }
+    // Each repo is shown as soon as it's in, so how old any cached data is
+    // can only be told at the end.
+    api.fetch_repos(config, config_repos, state, |config_repo, repo| {
+        let repo = match repo {
+            Ok(repo) => repo,
+            Err(e) => {
+                display.repo(config_repo);
+                display.repo_error(&PearsError::from(e));
+                return;
+            }
+        };
+        warn_rate_limit(config, repo.rate_limit.as_ref(), &display);
         let mut prs: Vec<&PullRequest> = repo
             .pull_requests
+            .nodes
             .iter()
-            .filter(|pr| !only_me || pr.author.login == config.me)
+            .filter(|pr| filters.matches(config, pr))
             .collect();
-        prs.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        prs.sort_by_key(|pr| cmp::Reverse(pr.updated_at));
+        let (prs, local) = local_branches(config, filters, config_repo, prs);
         display.repo(config_repo);
-        display.list(prs);
+        display.list(prs, config, fork, &local);
+    })?;
+    if let Some(timestamp) = api.data_as_of() {
+        display.data_as_of(timestamp);
+    }
+    Ok(())
+}
+
+fn search<T: GithubAPI>(
+    config: &Config,
+    local_repo: &ConfigRepo,
+    filters: &Filters,
+    api: T,
+    display: PearsDisplay,
+    query: &str,
+) -> Result<(), PearsError> {
+    let prs = api.search_pull_requests(config, local_repo, query)?;
+    warn_rate_limit(
+        config,
+        api.rate_limit(config, local_repo).as_ref(),
+        &display,
+    );
+    if let Some(timestamp) = api.data_as_of() {
+        display.data_as_of(timestamp);
+    }
+
+    // Grouped by repository in the order results first mention them.
+    let mut groups: Vec<(ConfigRepo, Vec<&PullRequest>)> = vec![];
+    for pr in prs.iter().filter(|pr| filters.matches(config, pr)) {
+        let repo = match pr.repository {
+            Some(ref repository) => ConfigRepo {
+                owner: repository.owner.login.clone(),
+                name: repository.name.clone(),
+                ..local_repo.clone()
+            },
+            None => local_repo.clone(),
+        };
+        match groups
+            .iter()
+            .position(|(r, _)| r.owner == repo.owner && r.name == repo.name)
+        {
+            Some(i) => groups[i].1.push(pr),
+            None => groups.push((repo, vec![pr])),
+        }
+    }
+    for (repo, prs) in groups {
+        let (prs, local) = local_branches(config, filters, &repo, prs);
+        if prs.is_empty() {
+            continue;
+        }
+        display.repo(&repo);
+        display.list(prs, config, None, &local);
     }
     Ok(())
 }
 
-fn show<T: GithubAPI>(
+fn show<T: GithubAPI + Sync>(
     config: &Config,
-    config_repos: &Vec<ConfigRepo>,
+    config_repos: &[ConfigRepo],
     api: T,
     display: PearsDisplay,
     number: i32,
+    unresolved_only: bool,
 ) -> Result<(), PearsError> {
-    for config_repo in config_repos {
-        let repo = api
-            .fetch_repo(config, &config_repo)
-            .expect("Could not reach GitHub API.");
-        let pr = repo
-            .pull_requests
-            .into_iter()
-            .find(|pr| pr.number == number);
-        match pr {
-            Some(pr) => {
-                display.show(pr).unwrap();
-                return Ok(());
+    match find_pull_request(config, config_repos, &api, number)? {
+        Some((config_repo, pr)) => {
+            warn_rate_limit(
+                config,
+                api.rate_limit(config, config_repo).as_ref(),
+                &display,
+            );
+            if let Some(timestamp) = api.data_as_of() {
+                display.data_as_of(timestamp);
             }
-            None => (),
+            display.show(pr, unresolved_only).unwrap();
+            Ok(())
+        }
+        None => Err(PearsError {
+            details: format!("No PR found with number {}.", number),
+        }),
+    }
+}
+
+fn set_draft<T: GithubAPI + Sync>(
+    config: &Config,
+    config_repos: &[ConfigRepo],
+    api: T,
+    display: PearsDisplay,
+    number: i32,
+    draft: bool,
+) -> Result<(), PearsError> {
+    match find_pull_request(config, config_repos, &api, number)? {
+        // Sent even if the pull request looks done already, since it may
+        // come from the cache.
+        Some((config_repo, pr)) => {
+            let is_draft = api.set_draft(config, config_repo, &pr.id, draft)?;
+            display.draft_status(number, is_draft);
+            Ok(())
         }
+        None => Err(PearsError {
+            details: format!("No PR found with number {}.", number),
+        }),
+    }
+}
+
+fn review<T: GithubAPI + Sync>(
+    config: &Config,
+    config_repos: &[ConfigRepo],
+    api: T,
+    display: PearsDisplay,
+    number: i32,
+    event: ReviewEvent,
+    message: Option<&str>,
+) -> Result<(), PearsError> {
+    let (config_repo, pr) = match find_pull_request(config, config_repos, &api, number)? {
+        Some(found) => found,
+        None => {
+            return Err(PearsError {
+                details: format!("No PR found with number {}.", number),
+            })
+        }
+    };
+    let body = match message {
+        Some(message) => String::from(message),
+        None => editor::edit(
+            "REVIEW_MESSAGE",
+            &format!(
+                "\n# Reviewing #{}: {}\n# Lines starting with '#' are ignored.\n",
+                pr.number, pr.title
+            ),
+        )?,
+    };
+    if body.is_empty() && event.requires_body() {
+        return Err(PearsError {
+            details: String::from("Aborting review due to empty message."),
+        });
+    }
+    api.add_review(config, config_repo, &pr.id, event, &body)?;
+    display.review_submitted(number, event);
+    Ok(())
+}
+
+struct MergeOptions {
+    method: MergeMethod,
+    delete_branch: bool,
+    /// Merge even if something should block it.
+    force: bool,
+}
+
+fn merge<T: GithubAPI + Sync>(
+    config: &Config,
+    config_repos: &[ConfigRepo],
+    api: T,
+    display: PearsDisplay,
+    number: i32,
+    options: &MergeOptions,
+) -> Result<(), PearsError> {
+    let (config_repo, pr) = match find_pull_request(config, config_repos, &api, number)? {
+        Some(found) => found,
+        None => {
+            return Err(PearsError {
+                details: format!("No PR found with number {}.", number),
+            })
+        }
+    };
+    let blockers = pr.merge_blockers();
+    if !blockers.is_empty() {
+        let reasons = blockers
+            .iter()
+            .map(|b| format!("  - {}", b))
+            .collect::<Vec<String>>()
+            .join("\n");
+        if !options.force {
+            return Err(PearsError {
+                details: format!(
+                    "Not merging #{}:\n{}\nUse --force to merge anyway.",
+                    number, reasons
+                ),
+            });
+        }
+        display.forced_merge(number, &reasons);
+    }
+
+    api.merge_pull_request(
+        config,
+        config_repo,
+        &pr.id,
+        options.method,
+        pr.head_ref_oid.as_deref(),
+    )?;
+    display.merged(number, options.method);
+
+    if options.delete_branch {
+        if let (Some(head_ref), Some(name)) = (pr.head_ref.as_ref(), pr.head_ref_name.as_ref()) {
+            // Like GitHub's own button, leave a fork's branch to its owner.
+            if pr.is_cross_repository {
+                display.branch_kept(name);
+            } else {
+                api.delete_ref(config, config_repo, &head_ref.id)?;
+                display.branch_deleted(name);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn checkout<T: GithubAPI + Sync>(
+    config: &Config,
+    repos: &[ConfigRepo],
+    api: T,
+    display: PearsDisplay,
+    number: i32,
+) -> Result<(), PearsError> {
+    let (config_repo, pr) = match find_pull_request(config, repos, &api, number)? {
+        Some(found) => found,
+        None => {
+            return Err(PearsError {
+                details: format!("No PR found with number {}.", number),
+            })
+        }
+    };
+    let branch = config.checkout_branch_name(&pr);
+    let cwd = env::current_dir().expect("Could not get current dir.");
+    let remote = remote_for(cwd.clone(), config_repo, config.remote.as_deref())?;
+    checkout_pull_request(cwd, &remote, &pr, &branch, config.token_for(config_repo))?;
+    display.checked_out(number, &branch);
+    Ok(())
+}
+
+/// Tries merging a pull request into its base branch locally, to tell which
+/// files conflict.
+fn conflicts<T: GithubAPI + Sync>(
+    config: &Config,
+    repos: &[ConfigRepo],
+    api: T,
+    display: PearsDisplay,
+    number: i32,
+) -> Result<(), PearsError> {
+    let (config_repo, pr) = match find_pull_request(config, repos, &api, number)? {
+        Some(found) => found,
+        None => {
+            return Err(PearsError {
+                details: format!("No PR found with number {}.", number),
+            })
+        }
+    };
+    let (base_name, base_oid, head_oid) =
+        match (&pr.base_ref_name, &pr.base_ref_oid, &pr.head_ref_oid) {
+            (Some(base_name), Some(base_oid), Some(head_oid)) => (base_name, base_oid, head_oid),
+            _ => {
+                return Err(PearsError {
+                    details: format!("GitHub didn't say which commits #{} merges.", number),
+                })
+            }
+        };
+    let cwd = env::current_dir().expect("Could not get current dir.");
+    let remote = remote_for(cwd.clone(), config_repo, config.remote.as_deref())?;
+    let paths = merge_conflicts(
+        cwd,
+        &remote,
+        number,
+        base_name,
+        base_oid,
+        head_oid,
+        config.token_for(config_repo),
+    )?;
+    display.merge_conflicts(number, base_name, &paths);
+    Ok(())
+}
+
+enum DiffMode {
+    Patch,
+    Stat,
+    NameOnly,
+}
+
+/// Shows the changes a pull request makes, to the files under `paths` if any
+/// are given.
+fn diff<T: GithubAPI>(
+    config: &Config,
+    local_repo: &ConfigRepo,
+    api: T,
+    display: PearsDisplay,
+    number: i32,
+    paths: &[&str],
+    mode: DiffMode,
+) -> Result<(), PearsError> {
+    let files: Vec<_> = api
+        .fetch_changed_files(config, local_repo, number)?
+        .into_iter()
+        .filter(|file| paths.is_empty() || paths.iter().any(|path| file.is_under(path)))
+        .collect();
+    match mode {
+        DiffMode::Patch => display.diff(&files),
+        DiffMode::Stat => display.diff_stat(&files),
+        DiffMode::NameOnly => display.diff_names(&files),
     }
-    Err(PearsError {
-        details: format!("No active PR found with number {}.", number),
+    .map_err(|e| PearsError {
+        details: e.to_string(),
     })
 }
 
+fn rate_limit<T: GithubAPI>(
+    config: &Config,
+    local_repo: &ConfigRepo,
+    api: T,
+    display: PearsDisplay,
+) -> Result<(), PearsError> {
+    let rate_limit = api.fetch_rate_limit(config, local_repo)?;
+    display.rate_limit(&rate_limit);
+    Ok(())
+}
+
 fn show_config(config: &Config) -> Result<(), PearsError> {
     println!("{:?}", config);
     Ok(())
@@ -94,7 +461,7 @@ fn relevant_repos(
                     .iter()
                     .find(|&g| g.name == group_name)
                     .expect("Could not find group with that name. Please check your config.");
-                group.repos.clone()
+                group.resolved_repos()
             }
             None => vec![local_repo],
         },
@@ -103,6 +470,93 @@ fn relevant_repos(
     Ok(config_repos)
 }
 
+/// The repo pull requests are made against from the local checkout: in a fork,
+/// its parent, unless the config or `--fork-only` says to stay in the fork.
+/// The fork is returned along with its parent.
+fn upstream_repo<T: GithubAPI>(
+    config: &Config,
+    api: &T,
+    local_repo: ConfigRepo,
+) -> Result<(ConfigRepo, Option<ConfigRepo>), PearsError> {
+    if config.fork_only.unwrap_or(false) {
+        return Ok((local_repo, None));
+    }
+    match api.fetch_parent(config, &local_repo)? {
+        Some(parent) => Ok((parent, Some(local_repo))),
+        None => Ok((local_repo, None)),
+    }
+}
+
+/// The repos to list: a group's, or else the local checkout's, as
+/// `upstream_repo` finds it.
+fn listed_repos<T: GithubAPI>(
+    config: &Config,
+    api: &T,
+    local_repo: ConfigRepo,
+    group: Option<&str>,
+) -> Result<(Vec<ConfigRepo>, Option<ConfigRepo>), PearsError> {
+    if group.is_some() {
+        return Ok((relevant_repos(config, local_repo, group)?, None));
+    }
+    let (repo, fork) = upstream_repo(config, api, local_repo)?;
+    Ok((vec![repo], fork))
+}
+
+/// The group and pull request given to a per-PR command. A lone argument is
+/// the pull request rather than a group, if it parses as one.
+fn pull_request_args<'a>(matches: &'a ArgMatches) -> (Option<&'a str>, Option<&'a str>) {
+    match (matches.value_of("group"), matches.value_of("number")) {
+        (Some(reference), None) if parse_pull_request_reference(reference).is_ok() => {
+            (None, Some(reference))
+        }
+        args => args,
+    }
+}
+
+/// The repos to look for a pull request in, and its number. A reference naming
+/// a repo overrides the group. Without one, it is the pull request for the
+/// branch checked out, in the local repo.
+fn pull_request_target<T: GithubAPI>(
+    config: &Config,
+    local_repo: ConfigRepo,
+    api: &T,
+    group: Option<&str>,
+    number: Option<&str>,
+) -> Result<(Vec<ConfigRepo>, i32), PearsError> {
+    if let Some(reference) = number {
+        let reference = parse_pull_request_reference(reference)?;
+        let repos = match reference.repo {
+            Some(repo) => vec![repo],
+            None => listed_repos(config, api, local_repo, group)?.0,
+        };
+        return Ok((repos, reference.number));
+    }
+    if let Some(group) = group {
+        return Err(PearsError {
+            details: format!("Which pull request in {}? Give its number.", group),
+        });
+    }
+
+    let cwd = env::current_dir().expect("Could not get current dir.");
+    let head = head_branch(cwd)?.ok_or_else(|| PearsError {
+        details: String::from("Not on a branch. Give the number of a pull request."),
+    })?;
+    let (repo, _) = upstream_repo(config, api, local_repo)?;
+    let branch = match head {
+        HeadBranch::PullRequest(number) => return Ok((vec![repo], number)),
+        HeadBranch::Named(branch) => branch,
+    };
+    match find_branch_pull_request(config, &repo, api, &branch)? {
+        Some(pr) => Ok((vec![repo], pr.number)),
+        None => Err(PearsError {
+            details: format!(
+                "No pull request found for branch {} in {}/{}.",
+                branch, repo.owner, repo.name
+            ),
+        }),
+    }
+}
+
 fn main() {
     let matches = App::new("pears")
         .version("1.2.5")
@@ -120,68 +574,429 @@ fn main() {
             Arg::with_name("repo")
                 .short("r")
                 .long("repo")
-                .help("Specify a repository. Format: <owner>/<repo>")
+                .help("Specify a repository. Format: [<host>/]<owner>/<repo>")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("remote")
+                .long("remote")
+                .value_name("NAME")
+                .help("Use the repository of this git remote (default: upstream, then origin)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("fork-only")
+                .long("fork-only")
+                .help("In a fork, use the fork instead of the repository it was forked from"),
+        )
+        .arg(
+            Arg::with_name("limit")
+                .short("l")
+                .long("limit")
+                .value_name("N")
+                .help("Fetch at most N pull requests per repository")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("concurrency")
+                .short("j")
+                .long("concurrency")
+                .value_name("N")
+                .help("Fetch up to N repositories at once")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("refresh")
+                .long("refresh")
+                .help("Ignore cached responses"),
+        )
+        .arg(
+            Arg::with_name("offline")
+                .long("offline")
+                .conflicts_with("refresh")
+                .help("Only use cached responses"),
+        )
         .arg(
             Arg::with_name("mine")
                 .short("m")
                 .long("mine")
                 .help("Show only pull requests authored by me."),
         )
+        .arg(
+            Arg::with_name("review-requested")
+                .long("review-requested")
+                .value_name("WHO")
+                .help("Show only pull requests awaiting review from me, a user or a team slug.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("hide-drafts")
+                .long("hide-drafts")
+                .conflicts_with("only-drafts")
+                .help("Hide drafts and pull requests that look like work in progress."),
+        )
+        .arg(
+            Arg::with_name("local-only")
+                .long("local-only")
+                .help("Show only pull requests with a local branch."),
+        )
+        .arg(
+            Arg::with_name("only-drafts")
+                .long("only-drafts")
+                .help("Show only drafts and pull requests that look like work in progress."),
+        )
         .subcommand(
             SubCommand::with_name("list")
                 .about("lists active pull requests")
-                .arg(Arg::with_name("group").required(false).index(1)),
+                .arg(Arg::with_name("group").required(false).index(1))
+                .arg(
+                    Arg::with_name("state")
+                        .short("s")
+                        .long("state")
+                        .help("List pull requests in this state")
+                        .takes_value(true)
+                        .possible_values(&["open", "closed", "merged", "all"])
+                        .default_value("open"),
+                ),
         )
         .subcommand(
             SubCommand::with_name("show")
                 .about("details for a pull request")
-                .arg(Arg::with_name("number").required(true).index(2))
+                .arg(Arg::with_name("number").required(false).index(2))
+                .arg(Arg::with_name("group").required(false).index(1))
+                .arg(
+                    Arg::with_name("unresolved")
+                        .long("unresolved")
+                        .help("Show only unresolved review threads"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("search")
+                .about("searches pull requests across repositories")
+                .arg(
+                    Arg::with_name("query")
+                        .required(true)
+                        .multiple(true)
+                        .help("GitHub search syntax, e.g. review-requested:@me org:acme"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("review")
+                .about("submits a review of a pull request")
+                .arg(Arg::with_name("number").required(false).index(2))
+                .arg(Arg::with_name("group").required(false).index(1))
+                .arg(
+                    Arg::with_name("approve")
+                        .short("a")
+                        .long("approve")
+                        .help("Approve the pull request"),
+                )
+                .arg(
+                    Arg::with_name("request-changes")
+                        .short("r")
+                        .long("request-changes")
+                        .help("Request changes to the pull request"),
+                )
+                .arg(
+                    Arg::with_name("comment")
+                        .short("c")
+                        .long("comment")
+                        .help("Comment without approving or requesting changes"),
+                )
+                .group(
+                    ArgGroup::with_name("event")
+                        .args(&["approve", "request-changes", "comment"])
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("message")
+                        .short("m")
+                        .long("message")
+                        .value_name("MESSAGE")
+                        .help("Use MESSAGE as the review body instead of opening $EDITOR")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("checkout")
+                .about("checks out a pull request in a local branch")
+                .arg(Arg::with_name("number").required(false).index(1)),
+        )
+        .subcommand(
+            SubCommand::with_name("conflicts")
+                .about("lists the files a pull request conflicts in, merging it locally")
+                .arg(Arg::with_name("number").required(false).index(1)),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("shows the changes a pull request makes")
+                .arg(Arg::with_name("number").required(false).index(1))
+                .arg(
+                    Arg::with_name("paths")
+                        .multiple(true)
+                        .index(2)
+                        .help("Only show changes to these files or directories"),
+                )
+                .arg(
+                    Arg::with_name("stat")
+                        .long("stat")
+                        .help("Only show how many lines changed in each file"),
+                )
+                .arg(
+                    Arg::with_name("name-only")
+                        .long("name-only")
+                        .conflicts_with("stat")
+                        .help("Only show the names of changed files"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("merge")
+                .about("merges a pull request once it is ready")
+                .arg(Arg::with_name("number").required(false).index(2))
+                .arg(Arg::with_name("group").required(false).index(1))
+                .arg(
+                    Arg::with_name("merge")
+                        .long("merge")
+                        .help("Create a merge commit (default)"),
+                )
+                .arg(
+                    Arg::with_name("squash")
+                        .long("squash")
+                        .help("Squash the commits into one"),
+                )
+                .arg(
+                    Arg::with_name("rebase")
+                        .long("rebase")
+                        .help("Rebase the commits onto the base branch"),
+                )
+                .group(ArgGroup::with_name("method").args(&["merge", "squash", "rebase"]))
+                .arg(
+                    Arg::with_name("delete-branch")
+                        .short("d")
+                        .long("delete-branch")
+                        .help("Delete the head branch after merging, unless it is in a fork"),
+                )
+                .arg(
+                    Arg::with_name("force")
+                        .short("f")
+                        .long("force")
+                        .help("Merge even if it isn't approved, passing and mergeable"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("ready")
+                .about("marks a draft pull request ready for review")
+                .arg(Arg::with_name("number").required(false).index(2))
+                .arg(Arg::with_name("group").required(false).index(1)),
+        )
+        .subcommand(
+            SubCommand::with_name("draft")
+                .about("converts a pull request to a draft")
+                .arg(Arg::with_name("number").required(false).index(2))
                 .arg(Arg::with_name("group").required(false).index(1)),
         )
         .subcommand(SubCommand::with_name("config").about("Show config"))
+        .subcommand(SubCommand::with_name("rate-limit").about("Show GitHub API rate limit usage"))
         .get_matches();
 
-    let config = read_config_file(matches.value_of("config").unwrap())
+    let mut config = read_config_file(matches.value_of("config").unwrap())
         .expect("Could not parse config file.");
+    if let Some(limit) = matches.value_of("limit") {
+        config.limit = Some(limit.parse::<usize>().expect("--limit must be a number."));
+    }
+    if let Some(concurrency) = matches.value_of("concurrency") {
+        config.concurrency = Some(
+            concurrency
+                .parse::<usize>()
+                .expect("--concurrency must be a number."),
+        );
+    }
 
-    let local_repo = if matches.is_present("repo") {
-        parse_repo_description(matches.value_of("repo").unwrap())
-    } else {
-        let cwd = env::current_dir().expect("Could not get current dir.");
-        discover_repo(cwd).expect("Could not determine repo details.")
+    if let Some(remote) = matches.value_of("remote") {
+        config.remote = Some(String::from(remote));
+    }
+    // A repo named with --repo is used as given, even if it is a fork.
+    if matches.is_present("fork-only") || matches.is_present("repo") {
+        config.fork_only = Some(true);
+    }
+
+    // Only worked out by the commands that need it, since some don't.
+    let repo_arg = matches.value_of("repo");
+    let local_repo = || match repo_arg {
+        Some(repo) => parse_repo_description(repo),
+        None => {
+            let cwd = env::current_dir().expect("Could not get current dir.");
+            discover_repo(cwd, config.remote.as_deref())
+        }
+    };
+    let filters = Filters {
+        only_me: matches.occurrences_of("mine") > 0,
+        review_requested: matches
+            .value_of("review-requested")
+            .map(|who| config.reviewers(who)),
+        hide_drafts: matches.is_present("hide-drafts"),
+        only_drafts: matches.is_present("only-drafts"),
+        local_only: matches.is_present("local-only"),
     };
-    let only_me = matches.occurrences_of("mine") > 0;
 
     let display = PearsDisplay::new();
-    let api = GitHubGraphqlAPI {};
+    let cache_mode = if matches.is_present("offline") {
+        CacheMode::Offline
+    } else if matches.is_present("refresh") {
+        CacheMode::Refresh
+    } else {
+        CacheMode::Normal
+    };
+    let api = GitHubGraphqlAPI::new(Cache::new(&config, cache_mode));
 
     let result = match matches.subcommand() {
         ("show", Some(matches)) => {
-            let number = matches
-                .value_of("number")
-                .map(|n| n.parse::<i32>())
+            let (group, number) = pull_request_args(matches);
+            let unresolved_only = matches.is_present("unresolved");
+            local_repo()
+                .and_then(|local_repo| {
+                    pull_request_target(&config, local_repo, &api, group, number)
+                })
+                .and_then(|(repos, number)| {
+                    show(&config, &repos, api, display, number, unresolved_only)
+                })
+        }
+        (subcommand @ "ready", Some(matches)) | (subcommand @ "draft", Some(matches)) => {
+            let (group, number) = pull_request_args(matches);
+            local_repo()
+                .and_then(|local_repo| {
+                    pull_request_target(&config, local_repo, &api, group, number)
+                })
+                .and_then(|(repos, number)| {
+                    set_draft(&config, &repos, api, display, number, subcommand == "draft")
+                })
+        }
+        ("search", Some(matches)) => {
+            let query = matches
+                .values_of("query")
                 .unwrap()
-                .unwrap();
-            let group = matches.value_of("group");
-            let repos = relevant_repos(&config, local_repo, group).unwrap();
-            show(&config, &repos, api, display, number)
+                .collect::<Vec<&str>>()
+                .join(" ");
+            // Searching only needs a host, so outside a checkout it is the
+            // configured one.
+            let repo = match local_repo() {
+                Err(_) if repo_arg.is_none() => Ok(ConfigRepo::default()),
+                repo => repo,
+            };
+            repo.and_then(|repo| search(&config, &repo, &filters, api, display, &query))
+        }
+        ("review", Some(matches)) => {
+            let (group, number) = pull_request_args(matches);
+            let event = if matches.is_present("approve") {
+                ReviewEvent::Approve
+            } else if matches.is_present("request-changes") {
+                ReviewEvent::RequestChanges
+            } else {
+                ReviewEvent::Comment
+            };
+            let message = matches.value_of("message");
+            local_repo()
+                .and_then(|local_repo| {
+                    pull_request_target(&config, local_repo, &api, group, number)
+                })
+                .and_then(|(repos, number)| {
+                    review(&config, &repos, api, display, number, event, message)
+                })
+        }
+        ("checkout", Some(matches)) => {
+            let number = matches.value_of("number");
+            local_repo()
+                .and_then(|local_repo| pull_request_target(&config, local_repo, &api, None, number))
+                .and_then(|(repos, number)| checkout(&config, &repos, api, display, number))
+        }
+        ("conflicts", Some(matches)) => {
+            let number = matches.value_of("number");
+            local_repo()
+                .and_then(|local_repo| pull_request_target(&config, local_repo, &api, None, number))
+                .and_then(|(repos, number)| conflicts(&config, &repos, api, display, number))
+        }
+        ("diff", Some(matches)) => {
+            let mut number = matches.value_of("number");
+            let mut paths: Vec<&str> = matches
+                .values_of("paths")
+                .map(|paths| paths.collect())
+                .unwrap_or_default();
+            // Without a number, the first path lands in its place.
+            if let Some(path) = number.filter(|n| parse_pull_request_reference(n).is_err()) {
+                paths.insert(0, path);
+                number = None;
+            }
+            let mode = if matches.is_present("stat") {
+                DiffMode::Stat
+            } else if matches.is_present("name-only") {
+                DiffMode::NameOnly
+            } else {
+                DiffMode::Patch
+            };
+            local_repo()
+                .and_then(|local_repo| pull_request_target(&config, local_repo, &api, None, number))
+                .and_then(|(repos, number)| {
+                    diff(&config, &repos[0], api, display, number, &paths, mode)
+                })
+        }
+        ("merge", Some(matches)) => {
+            let (group, number) = pull_request_args(matches);
+            let method = if matches.is_present("squash") {
+                MergeMethod::Squash
+            } else if matches.is_present("rebase") {
+                MergeMethod::Rebase
+            } else {
+                MergeMethod::Merge
+            };
+            let options = MergeOptions {
+                method,
+                delete_branch: matches.is_present("delete-branch"),
+                force: matches.is_present("force"),
+            };
+            local_repo()
+                .and_then(|local_repo| {
+                    pull_request_target(&config, local_repo, &api, group, number)
+                })
+                .and_then(|(repos, number)| merge(&config, &repos, api, display, number, &options))
         }
         ("config", _matches) => show_config(&config),
+        ("rate-limit", _matches) => {
+            local_repo().and_then(|local_repo| rate_limit(&config, &local_repo, api, display))
+        }
         (_, Some(matches)) => {
             let group = matches.value_of("group");
-            let repos = relevant_repos(&config, local_repo, group).unwrap();
-            list(&config, &repos, only_me, api, display)
-        }
-        (_, None) => {
-            let repos = relevant_repos(&config, local_repo, None).unwrap();
-            list(&config, &repos, only_me, api, display)
+            let state = matches
+                .value_of("state")
+                .map_or(Ok(StateFilter::Open), |s| s.parse::<StateFilter>());
+            state.and_then(|state| {
+                let (repos, fork) = listed_repos(&config, &api, local_repo()?, group)?;
+                list(
+                    &config,
+                    &repos,
+                    fork.as_ref(),
+                    &filters,
+                    state,
+                    api,
+                    display,
+                )
+            })
         }
+        (_, None) => local_repo()
+            .and_then(|local_repo| listed_repos(&config, &api, local_repo, None))
+            .and_then(|(repos, fork)| {
+                list(
+                    &config,
+                    &repos,
+                    fork.as_ref(),
+                    &filters,
+                    StateFilter::Open,
+                    api,
+                    display,
+                )
+            }),
     };
 
-    match result.err() {
-        Some(error) => println!("{}", error.details),
-        None => (),
-    };
+    if let Err(error) = result {
+        println!("{}", error.details);
+    }
 }
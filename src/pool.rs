@@ -0,0 +1,97 @@
+use std::cmp;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// Runs `work` over `jobs` on up to `concurrency` threads. Each result is
+/// passed to `handle` as soon as it and the results of every earlier job have
+/// arrived, so results are handled in job order. No further jobs are started
+/// once `handle` returns `false` or an error, or `work` fails.
+pub fn in_order<'a, J, R, E, W, H>(
+    jobs: &'a [J],
+    concurrency: usize,
+    work: W,
+    mut handle: H,
+) -> Result<(), E>
+where
+    J: Sync,
+    R: Send,
+    E: Send,
+    W: Fn(&J) -> Result<R, E> + Sync,
+    H: FnMut(&'a J, R) -> Result<bool, E>,
+{
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..cmp::min(cmp::max(concurrency, 1), jobs.len()) {
+            let sender = sender.clone();
+            let (next, stop, work) = (&next, &stop, &work);
+            scope.spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    if index >= jobs.len() {
+                        break;
+                    }
+                    if sender.send((index, work(&jobs[index]))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        // Moved in so that returning early hangs up on the workers.
+        let receiver = receiver;
+        let mut ready = BTreeMap::new();
+        let mut handled = 0;
+        for (index, result) in receiver.iter() {
+            ready.insert(index, result);
+            while let Some(result) = ready.remove(&handled) {
+                let keep_going = result.and_then(|r| handle(&jobs[handled], r));
+                handled += 1;
+                match keep_going {
+                    Ok(true) => (),
+                    Ok(false) => {
+                        stop.store(true, Ordering::SeqCst);
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        stop.store(true, Ordering::SeqCst);
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn handles_results_in_order() {
+        let jobs: Vec<u64> = (0..8).collect();
+        let mut seen = vec![];
+        let result: Result<(), ()> = in_order(
+            &jobs,
+            3,
+            |&job| {
+                // Later jobs finish first.
+                thread::sleep(Duration::from_millis(40 - job * 5));
+                Ok(job * 10)
+            },
+            |_, result| {
+                seen.push(result);
+                Ok(seen.len() < 5)
+            },
+        );
+        assert!(result.is_ok());
+        assert_eq!(seen, vec![0, 10, 20, 30, 40]);
+    }
+}
@@ -1,9 +1,22 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Deserializer};
+use serde_json;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
 #[derive(Debug, Clone)]
-pub struct GitHubError {
-    pub details: String,
+pub enum GitHubError {
+    Authentication(String),
+    /// The token is valid but may not access the resource, e.g. an organisation
+    /// enforcing SAML single sign-on.
+    Forbidden(String),
+    NotFound(String),
+    InsufficientScopes(String),
+    RateLimited(String),
+    Network(String),
+    MalformedResponse(String),
+    NotCached(String),
 }
 
 #[derive(Debug, Clone)]
@@ -11,28 +24,79 @@ pub struct PearsError {
     pub details: String,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, Default)]
 pub struct ConfigRepo {
     pub owner: String,
     pub name: String,
+    pub host: Option<String>,
+    pub api_url: Option<String>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+/// A pull request as given on the command line: a number, optionally in a
+/// repo other than the one in context.
+#[derive(Debug, Clone)]
+pub struct PullRequestReference {
+    pub repo: Option<ConfigRepo>,
+    pub number: i32,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
 pub struct Config {
     pub me: String,
     pub token: String,
+    pub host: Option<String>,
+    pub api_url: Option<String>,
+    pub tokens: Option<HashMap<String, String>>,
     pub groups: Option<Vec<Group>>,
+    pub limit: Option<usize>,
+    pub concurrency: Option<usize>,
+    pub cache_ttl: Option<i64>,
+    pub rate_limit_warning: Option<i32>,
+    pub rate_limit_wait: Option<bool>,
+    pub teams: Option<Vec<String>>,
+    pub wip_title_prefixes: Option<Vec<String>>,
+    pub wip_labels: Option<Vec<String>>,
+    pub checkout_branch: Option<String>,
+    pub remote: Option<String>,
+    pub fork_only: Option<bool>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct Group {
     pub name: String,
+    pub host: Option<String>,
+    pub api_url: Option<String>,
     pub repos: Vec<ConfigRepo>,
 }
 
+/// The envelope of every GraphQL response. `data` is left undecoded until
+/// `errors` has been checked, since failed queries tend to null out fields the
+/// typed responses require.
 #[derive(Deserialize, Debug)]
 pub struct GraphqlResponse {
-    pub data: RepoResponse,
+    pub data: Option<serde_json::Value>,
+
+    #[serde(default)]
+    pub errors: Vec<GraphqlError>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GraphqlError {
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub message: String,
+    #[serde(default)]
+    pub path: Vec<serde_json::Value>,
+}
+
+/// Query data alongside the `rateLimit` every query asks for.
+#[derive(Deserialize, Debug)]
+pub struct RateLimitedResponse<T> {
+    #[serde(flatten)]
+    pub data: T,
+
+    #[serde(rename = "rateLimit")]
+    pub rate_limit: Option<RateLimit>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -40,25 +104,254 @@ pub struct RepoResponse {
     pub repository: Repo,
 }
 
+#[derive(Deserialize, Debug)]
+pub struct RepositoryParentResponse {
+    pub repository: RepositoryParent,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
-pub struct Repo {
-    pub name: String,
+pub struct RepositoryParent {
+    pub is_fork: bool,
+    /// Null for forks whose parent is gone or can't be seen.
+    pub parent: Option<PullRequestRepository>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimit {
+    pub limit: i32,
+    pub cost: i32,
+    pub remaining: i32,
+    pub reset_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchResponse {
+    #[serde(deserialize_with = "deserialize_pagination")]
+    pub search: Paginated<PullRequest>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PullRequestResponse {
+    pub repository: RepoPullRequest,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RepoPullRequest {
+    pub pull_request: PullRequest,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NodeResponse<T> {
+    pub node: T,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PullRequestComments {
+    #[serde(deserialize_with = "deserialize_pagination")]
+    pub comments: Paginated<Comment>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PullRequestReviews {
+    #[serde(deserialize_with = "deserialize_pagination")]
+    pub reviews: Paginated<Review>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PullRequestReviewThreads {
+    #[serde(deserialize_with = "deserialize_pagination")]
+    pub review_threads: Paginated<ReviewThread>,
+}
 
+/// The comments of a review thread.
+#[derive(Deserialize, Debug)]
+pub struct ReviewComments {
     #[serde(deserialize_with = "deserialize_pagination")]
-    pub pull_requests: Vec<PullRequest>,
+    pub comments: Paginated<Comment>,
 }
 
 #[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Repo {
+    #[serde(deserialize_with = "deserialize_pagination")]
+    pub pull_requests: Paginated<PullRequest>,
+
+    /// The rate limit as of the last query made to fetch this repository.
+    #[serde(skip)]
+    pub rate_limit: Option<RateLimit>,
+}
+
+/// Which pull requests to list, by state.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StateFilter {
+    Open,
+    Closed,
+    Merged,
+    All,
+}
+
+impl StateFilter {
+    /// The `PullRequestState` values to ask GitHub for.
+    pub fn graphql_states(&self) -> Vec<&'static str> {
+        match *self {
+            StateFilter::Open => vec!["OPEN"],
+            StateFilter::Closed => vec!["CLOSED"],
+            StateFilter::Merged => vec!["MERGED"],
+            StateFilter::All => vec!["OPEN", "CLOSED", "MERGED"],
+        }
+    }
+}
+
+impl FromStr for StateFilter {
+    type Err = PearsError;
+
+    fn from_str(s: &str) -> Result<StateFilter, PearsError> {
+        match s {
+            "open" => Ok(StateFilter::Open),
+            "closed" => Ok(StateFilter::Closed),
+            "merged" => Ok(StateFilter::Merged),
+            "all" => Ok(StateFilter::All),
+            _ => Err(PearsError {
+                details: format!(
+                    "Unknown state \"{}\". Expected open, closed, merged or all.",
+                    s
+                ),
+            }),
+        }
+    }
+}
+
+/// What a submitted review says about a pull request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReviewEvent {
+    Approve,
+    RequestChanges,
+    Comment,
+}
+
+impl ReviewEvent {
+    /// The `PullRequestReviewEvent` to send.
+    pub fn graphql_event(&self) -> &'static str {
+        match *self {
+            ReviewEvent::Approve => "APPROVE",
+            ReviewEvent::RequestChanges => "REQUEST_CHANGES",
+            ReviewEvent::Comment => "COMMENT",
+        }
+    }
+
+    /// Only approvals can be submitted without a body.
+    pub fn requires_body(&self) -> bool {
+        *self != ReviewEvent::Approve
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MergeMethod {
+    Merge,
+    Squash,
+    Rebase,
+}
+
+impl MergeMethod {
+    /// The `PullRequestMergeMethod` to send.
+    pub fn graphql_method(&self) -> &'static str {
+        match *self {
+            MergeMethod::Merge => "MERGE",
+            MergeMethod::Squash => "SQUASH",
+            MergeMethod::Rebase => "REBASE",
+        }
+    }
+}
+
+/// A reason not to merge a pull request yet.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeBlocker {
+    NotOpen(String),
+    Draft,
+    Conflicting,
+    MergeabilityUnknown,
+    ChangesRequested,
+    NotApproved,
+    ChecksFailing(Vec<String>),
+    ChecksPending(Vec<String>),
+}
+
+impl fmt::Display for MergeBlocker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MergeBlocker::NotOpen(ref state) => write!(f, "It is {}.", state.to_lowercase()),
+            MergeBlocker::Draft => write!(f, "It is a draft."),
+            MergeBlocker::Conflicting => write!(f, "It conflicts with the base branch."),
+            MergeBlocker::MergeabilityUnknown => write!(
+                f,
+                "GitHub hasn't worked out whether it can be merged yet. Try again shortly."
+            ),
+            MergeBlocker::ChangesRequested => write!(f, "Changes were requested."),
+            MergeBlocker::NotApproved => write!(f, "It hasn't been approved."),
+            MergeBlocker::ChecksFailing(ref checks) if checks.is_empty() => {
+                write!(f, "Checks are failing.")
+            }
+            MergeBlocker::ChecksFailing(ref checks) => {
+                write!(f, "Checks are failing: {}.", checks.join(", "))
+            }
+            MergeBlocker::ChecksPending(ref checks) if checks.is_empty() => {
+                write!(f, "Checks are still running.")
+            }
+            MergeBlocker::ChecksPending(ref checks) => {
+                write!(f, "Checks are still running: {}.", checks.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PageInfo {
+    pub has_next_page: bool,
+    pub end_cursor: Option<String>,
+}
+
+/// The nodes of a GraphQL connection along with the cursor needed to fetch the
+/// next page of it.
+#[derive(Debug)]
+pub struct Paginated<T> {
+    pub nodes: Vec<T>,
+    pub page_info: PageInfo,
+}
+
+impl<T> Default for Paginated<T> {
+    fn default() -> Paginated<T> {
+        Paginated {
+            nodes: vec![],
+            page_info: PageInfo::default(),
+        }
+    }
+}
+
+impl<T> Paginated<T> {
+    pub fn has_next_page(&self) -> bool {
+        self.page_info.has_next_page
+    }
+
+    /// Appends a subsequent page, taking over its cursor.
+    pub fn extend(&mut self, page: Paginated<T>) {
+        self.nodes.extend(page.nodes);
+        self.page_info = page.page_info;
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct User {
     pub login: String,
 }
 
 #[derive(Deserialize, Debug)]
-pub struct Commit {
-    pub sha: String,
-    pub user: User,
-    pub repo: Repo,
+pub struct Ref {
+    pub id: String,
 }
 
 #[derive(Deserialize, Debug)]
@@ -76,42 +369,413 @@ pub struct PullRequest {
     pub number: i32,
     pub url: String,
     pub mergeable: String,
+    #[serde(default)]
+    pub is_draft: bool,
+
+    #[serde(default)]
+    pub head_ref_name: Option<String>,
+    #[serde(default)]
+    pub is_cross_repository: bool,
+    /// Null once the repository the head branch is in has been deleted.
+    #[serde(default)]
+    pub head_repository: Option<PullRequestRepository>,
+    #[serde(default)]
+    pub head_ref_oid: Option<String>,
+    /// Null once the branch has been deleted.
+    #[serde(default)]
+    pub head_ref: Option<Ref>,
+    /// Only fetched for a single pull request.
+    #[serde(default)]
+    pub base_ref_name: Option<String>,
+    #[serde(default)]
+    pub base_ref_oid: Option<String>,
 
-    pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub closed_at: Option<DateTime<Utc>>,
     pub merged_at: Option<DateTime<Utc>>,
 
     pub author: User,
 
-    #[serde(deserialize_with = "deserialize_pagination")]
-    pub labels: Vec<Label>,
+    /// Only fetched when searching, since otherwise the repo is known.
+    #[serde(default)]
+    pub repository: Option<PullRequestRepository>,
 
     #[serde(deserialize_with = "deserialize_pagination")]
-    pub comments: Vec<Comment>,
+    pub labels: Paginated<Label>,
+
+    /// Left empty when listing pull requests.
+    #[serde(default, deserialize_with = "deserialize_pagination")]
+    pub comments: Paginated<Comment>,
 
     #[serde(deserialize_with = "deserialize_pagination")]
-    pub reviews: Vec<Review>,
+    pub reviews: Paginated<Review>,
+
+    /// Left empty when listing pull requests.
+    #[serde(default, deserialize_with = "deserialize_pagination")]
+    pub review_threads: Paginated<ReviewThread>,
+
+    /// Null unless branch protection requires reviews.
+    pub review_decision: Option<String>,
+
+    #[serde(default, deserialize_with = "deserialize_pagination")]
+    pub review_requests: Paginated<ReviewRequest>,
+
+    /// Only the head commit is fetched.
+    #[serde(default, deserialize_with = "deserialize_pagination")]
+    pub commits: Paginated<PullRequestCommit>,
 }
 
 impl PullRequest {
-    pub fn is_approved(&self) -> bool {
-        self.reviews.iter().any(|e| e.state == "APPROVED")
+    /// Everything that should stop the pull request being merged, which is
+    /// nothing if it is ready.
+    pub fn merge_blockers(&self) -> Vec<MergeBlocker> {
+        let mut blockers = vec![];
+        if self.state != "OPEN" {
+            blockers.push(MergeBlocker::NotOpen(self.state.clone()));
+        }
+        if self.is_draft {
+            blockers.push(MergeBlocker::Draft);
+        }
+        match self.mergeable.as_str() {
+            "CONFLICTING" => blockers.push(MergeBlocker::Conflicting),
+            "UNKNOWN" => blockers.push(MergeBlocker::MergeabilityUnknown),
+            _ => (),
+        }
+        match self.approval_state() {
+            ApprovalState::Approved => (),
+            ApprovalState::ChangesRequested => blockers.push(MergeBlocker::ChangesRequested),
+            ApprovalState::ReviewRequired | ApprovalState::None => {
+                blockers.push(MergeBlocker::NotApproved)
+            }
+        }
+
+        let checks = self
+            .status_check_rollup()
+            .map_or(&[][..], |rollup| &rollup.contexts.nodes[..]);
+        let named = |status: CiStatus| -> Vec<String> {
+            checks
+                .iter()
+                .filter(|c| c.status() == status)
+                .map(|c| String::from(c.name()))
+                .collect()
+        };
+        match self.ci_status() {
+            Some(CiStatus::Failing) => {
+                blockers.push(MergeBlocker::ChecksFailing(named(CiStatus::Failing)))
+            }
+            Some(CiStatus::Pending) => {
+                blockers.push(MergeBlocker::ChecksPending(named(CiStatus::Pending)))
+            }
+            Some(CiStatus::Passing) | None => (),
+        }
+        blockers
+    }
+
+    /// GitHub's review decision where branch protection makes one, otherwise
+    /// worked out from each reviewer's latest review.
+    pub fn approval_state(&self) -> ApprovalState {
+        match self.review_decision.as_deref() {
+            Some("APPROVED") => return ApprovalState::Approved,
+            Some("CHANGES_REQUESTED") => return ApprovalState::ChangesRequested,
+            Some("REVIEW_REQUIRED") => return ApprovalState::ReviewRequired,
+            _ => (),
+        }
+        let latest = self.latest_reviews();
+        if latest.iter().any(|r| r.state == "CHANGES_REQUESTED") {
+            ApprovalState::ChangesRequested
+        } else if latest.iter().any(|r| r.state == "APPROVED") {
+            ApprovalState::Approved
+        } else {
+            ApprovalState::None
+        }
+    }
+
+    /// Each reviewer's latest review, in the order they first reviewed. A
+    /// comment doesn't replace an earlier approval or request for changes,
+    /// just as it doesn't on GitHub.
+    pub fn latest_reviews(&self) -> Vec<&Review> {
+        let mut reviews: Vec<&Review> = self
+            .reviews
+            .nodes
+            .iter()
+            .filter(|r| r.state != "PENDING")
+            .collect();
+        reviews.sort_by_key(|r| r.created_at);
+
+        let mut latest: Vec<&Review> = vec![];
+        for review in reviews {
+            match latest
+                .iter()
+                .position(|r| r.author.login == review.author.login)
+            {
+                Some(i) => {
+                    if review.state != "COMMENTED" || latest[i].state == "COMMENTED" {
+                        latest[i] = review;
+                    }
+                }
+                None => latest.push(review),
+            }
+        }
+        latest
+    }
+
+    /// Whether a review is still requested from any of these users or teams.
+    /// GitHub drops a request once it is answered with a review.
+    pub fn is_review_requested_from(&self, reviewers: &[&str]) -> bool {
+        self.review_requests.nodes.iter().any(|request| {
+            request
+                .requested_reviewer
+                .as_ref()
+                .map_or(false, |r| reviewers.iter().any(|name| r.is_named(name)))
+        })
+    }
+
+    pub fn status_check_rollup(&self) -> Option<&StatusCheckRollup> {
+        self.commits
+            .nodes
+            .last()
+            .and_then(|c| c.commit.status_check_rollup.as_ref())
+    }
+
+    /// The combined CI status of the head commit, if it has any checks.
+    pub fn ci_status(&self) -> Option<CiStatus> {
+        self.status_check_rollup()
+            .map(|rollup| CiStatus::from_state(&rollup.state))
+    }
+
+    /// Whether the head branch is in `repo`.
+    pub fn is_from(&self, repo: &ConfigRepo) -> bool {
+        self.head_repository.as_ref().map_or(false, |head| {
+            head.owner.login.eq_ignore_ascii_case(&repo.owner)
+                && head.name.eq_ignore_ascii_case(&repo.name)
+        })
     }
 }
 
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct PullRequestRepository {
+    pub name: String,
+    pub owner: User,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ApprovalState {
+    Approved,
+    ChangesRequested,
+    ReviewRequired,
+    None,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewRequest {
+    /// Null when the reviewer can't be seen with the token's scopes.
+    pub requested_reviewer: Option<RequestedReviewer>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "__typename")]
+pub enum RequestedReviewer {
+    User {
+        login: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Team {
+        slug: String,
+        combined_slug: String,
+    },
+    #[serde(other)]
+    Other,
+}
+
+impl RequestedReviewer {
+    pub fn name(&self) -> Option<&str> {
+        match *self {
+            RequestedReviewer::User { ref login } => Some(login),
+            RequestedReviewer::Team {
+                ref combined_slug, ..
+            } => Some(combined_slug),
+            RequestedReviewer::Other => None,
+        }
+    }
+
+    /// Matches a user's login, or a team's slug with or without its
+    /// organization.
+    pub fn is_named(&self, name: &str) -> bool {
+        match *self {
+            RequestedReviewer::User { ref login } => login.eq_ignore_ascii_case(name),
+            RequestedReviewer::Team {
+                ref slug,
+                ref combined_slug,
+            } => slug.eq_ignore_ascii_case(name) || combined_slug.eq_ignore_ascii_case(name),
+            RequestedReviewer::Other => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CiStatus {
+    Passing,
+    Failing,
+    Pending,
+}
+
+impl CiStatus {
+    /// Maps a `StatusState`, as used by rollups and status contexts.
+    fn from_state(state: &str) -> CiStatus {
+        match state {
+            "SUCCESS" => CiStatus::Passing,
+            "PENDING" | "EXPECTED" => CiStatus::Pending,
+            _ => CiStatus::Failing,
+        }
+    }
+}
+
+/// What the branch checked out tracks on GitHub.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeadBranch {
+    /// A branch, whose pull request has to be looked up.
+    Named(String),
+    /// A pull request's `refs/pull/<n>/head`, as `pears checkout` tracks those
+    /// from forks.
+    PullRequest(i32),
+}
+
+/// How a local branch compares with a pull request's head.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LocalBranchState {
+    UpToDate,
+    Behind(usize),
+    Ahead(usize),
+    Diverged {
+        ahead: usize,
+        behind: usize,
+    },
+    /// The head commit hasn't been fetched, so the branch is behind at least.
+    Unfetched,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PullRequestCommit {
+    pub commit: HeadCommit,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct HeadCommit {
+    pub status_check_rollup: Option<StatusCheckRollup>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct StatusCheckRollup {
+    pub state: String,
+
+    /// Left empty when listing pull requests.
+    #[serde(default, deserialize_with = "deserialize_pagination")]
+    pub contexts: Paginated<StatusCheck>,
+}
+
+/// A check run from a GitHub App, or a commit status from the older statuses
+/// API.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "__typename")]
+pub enum StatusCheck {
+    CheckRun(CheckRun),
+    StatusContext(StatusContext),
+}
+
+impl StatusCheck {
+    pub fn name(&self) -> &str {
+        match *self {
+            StatusCheck::CheckRun(ref run) => &run.name,
+            StatusCheck::StatusContext(ref context) => &context.context,
+        }
+    }
+
+    pub fn status(&self) -> CiStatus {
+        match *self {
+            StatusCheck::CheckRun(ref run) => match (run.status.as_str(), &run.conclusion) {
+                ("COMPLETED", Some(conclusion)) => match conclusion.as_str() {
+                    "SUCCESS" | "NEUTRAL" | "SKIPPED" => CiStatus::Passing,
+                    _ => CiStatus::Failing,
+                },
+                _ => CiStatus::Pending,
+            },
+            StatusCheck::StatusContext(ref context) => CiStatus::from_state(&context.state),
+        }
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        match *self {
+            StatusCheck::CheckRun(_) => None,
+            StatusCheck::StatusContext(ref context) => context.description.as_deref(),
+        }
+    }
+
+    pub fn url(&self) -> Option<&str> {
+        match *self {
+            StatusCheck::CheckRun(ref run) => run.details_url.as_ref(),
+            StatusCheck::StatusContext(ref context) => context.target_url.as_ref(),
+        }
+        .map(|url| url.as_str())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckRun {
+    pub name: String,
+    pub status: String,
+    pub conclusion: Option<String>,
+    pub details_url: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusContext {
+    pub context: String,
+    pub state: String,
+    pub description: Option<String>,
+    pub target_url: Option<String>,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Review {
     pub author: User,
-    pub body_text: String,
     pub state: String,
 
-    #[serde(deserialize_with = "deserialize_pagination")]
-    pub comments: Vec<Comment>,
+    /// Left empty when listing pull requests.
+    #[serde(default)]
+    pub body_text: String,
 
     pub created_at: DateTime<Utc>,
-    pub updated_at: DateTime<Utc>,
+}
+
+/// A conversation on a line of the diff.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewThread {
+    pub id: String,
+    pub path: String,
+    /// Null when the line is no longer part of the diff.
+    pub line: Option<i32>,
+    pub is_resolved: bool,
+    pub is_outdated: bool,
+
+    #[serde(deserialize_with = "deserialize_pagination")]
+    pub comments: Paginated<Comment>,
+}
+
+impl ReviewThread {
+    /// The diff hunk the thread was started on.
+    pub fn diff_hunk(&self) -> Option<&str> {
+        self.comments
+            .nodes
+            .first()
+            .and_then(|c| c.diff_hunk.as_ref())
+            .map(|h| h.as_str())
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -120,18 +784,47 @@ pub struct Comment {
     pub author: User,
     pub body_text: String,
 
+    /// Only set on review comments.
+    #[serde(default)]
+    pub diff_hunk: Option<String>,
+
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
-pub fn deserialize_pagination<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+/// A file changed by a pull request, as returned by the REST API.
+#[derive(Deserialize, Debug)]
+pub struct ChangedFile {
+    pub filename: String,
+    /// "added", "removed", "modified", "renamed", ...
+    pub status: String,
+    pub additions: i32,
+    pub deletions: i32,
+    /// Missing for binary files and diffs too large to show.
+    pub patch: Option<String>,
+    pub previous_filename: Option<String>,
+}
+
+impl ChangedFile {
+    /// Whether the file is `path` or lies under it.
+    pub fn is_under(&self, path: &str) -> bool {
+        let path = path.trim_end_matches('/');
+        path.is_empty() || self.filename == path || self.filename.starts_with(&format!("{}/", path))
+    }
+}
+
+pub fn deserialize_pagination<'de, D, T>(deserializer: D) -> Result<Paginated<T>, D::Error>
 where
     D: Deserializer<'de>,
     T: Deserialize<'de>,
 {
     #[derive(Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
     pub struct GraphqlPagination<T> {
         pub edges: Vec<GraphqlPaginationNode<T>>,
+
+        #[serde(default)]
+        pub page_info: PageInfo,
     }
 
     #[derive(Deserialize, Debug)]
@@ -139,6 +832,8 @@ where
         pub node: T,
     }
 
-    GraphqlPagination::deserialize(deserializer)
-        .map(|p| p.edges.into_iter().map(|e| e.node).collect())
+    GraphqlPagination::deserialize(deserializer).map(|p| Paginated {
+        nodes: p.edges.into_iter().map(|e| e.node).collect(),
+        page_info: p.page_info,
+    })
 }